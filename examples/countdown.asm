@@ -0,0 +1,10 @@
+// Counts register 0 down to zero and stores every value in register 1
+.data 0 = 3
+
+    LDA (0)
+start:
+    BRZ 4
+    STA (1)
+    SUB #1
+    JMP start
+    NOP
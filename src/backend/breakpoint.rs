@@ -0,0 +1,26 @@
+use super::WatchpointHit;
+
+/// Position at which [`Runtime::run_until_break`](super::Runtime::run_until_break) stops
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Breakpoint {
+    /// Stops in front of every command generated from the source line (see [`Command::line`](crate::Command::line))
+    ///
+    /// `file` is an index into [`Program::files`](crate::Program::files), `0` for the
+    /// first file.
+    Line { file: usize, line: usize },
+    /// Stops in front of the command at the instruction index
+    Instruction(usize),
+}
+
+/// Reason why [`Runtime::run_until_break`](super::Runtime::run_until_break) returned
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopReason {
+    /// The next command has a breakpoint
+    Breakpoint(Breakpoint),
+    /// A watched register was accessed
+    Watchpoint(WatchpointHit),
+    /// The instruction counter left the program
+    Halted,
+    /// The maximum number of steps was executed
+    StepLimit,
+}
@@ -0,0 +1,68 @@
+use std::collections::VecDeque;
+
+use crate::nibble::Nibble;
+
+/// Number of steps the [`Runtime`](super::Runtime) remembers by default
+pub const DEFAULT_HISTORY_LIMIT: usize = 1024;
+
+/// State of the runtime before a single step
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct HistoryEntry {
+    pub step: usize,
+    pub accumulator: Nibble,
+    pub instruction_counter: usize,
+    /// Register written by the step and its previous value
+    pub register_write: Option<(usize, Nibble)>,
+}
+
+/// Bounded list of the most recent steps, the oldest entries are dropped first
+#[derive(Debug, Clone)]
+pub(crate) struct History {
+    entries: VecDeque<HistoryEntry>,
+    limit: usize,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            entries: VecDeque::new(),
+            limit: DEFAULT_HISTORY_LIMIT,
+        }
+    }
+}
+
+impl History {
+    pub fn push(&mut self, entry: HistoryEntry) {
+        if self.limit == 0 {
+            return;
+        }
+        while self.entries.len() >= self.limit {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub fn pop(&mut self) -> Option<HistoryEntry> {
+        self.entries.pop_back()
+    }
+
+    pub fn last_mut(&mut self) -> Option<&mut HistoryEntry> {
+        self.entries.back_mut()
+    }
+
+    /// Step of the oldest entry that can still be restored
+    pub fn oldest_step(&self) -> Option<usize> {
+        self.entries.front().map(|entry| entry.step)
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        while self.entries.len() > limit {
+            self.entries.pop_front();
+        }
+    }
+}
@@ -1,24 +1,69 @@
-use std::usize;
-
 use crate::commands::{Command, Instruction};
+use crate::encoding::encode;
+use crate::error::{EncodeError, RuntimeError};
 use crate::nibble::Nibble;
+use crate::program::Program;
 
+mod breakpoint;
+mod history;
 #[cfg(test)]
 mod test;
+mod trace;
+mod watchpoint;
+
+use std::collections::BTreeSet;
 
-#[derive(Debug, Clone, Copy)]
+pub use breakpoint::{Breakpoint, StopReason};
+pub use history::DEFAULT_HISTORY_LIMIT;
+use history::{History, HistoryEntry};
+pub use trace::{Trace, TraceRow};
+pub use watchpoint::{WatchCondition, Watchpoint, WatchpointHit};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct StatusBits {
     pub carry: bool,
     pub negative: bool,
     pub zero: bool,
 }
 
+/// Arrangement of program and data in the memory of the [`Runtime`]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum MemoryLayout {
+    /// The program and the registers are separate memories
+    #[default]
+    Harvard,
+    /// The encoded program and the data share one nibble address space
+    ///
+    /// The command at index `n` is stored at the addresses `2n` (opcode) and
+    /// `2n + 1` (operand), so `LDA (n)` and `STA (n)` can read and modify the program.
+    VonNeumann,
+}
+
+/// State of the program after a [`Runtime::step`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StepOutcome {
+    /// There are more commands to execute
+    Running,
+    /// The instruction counter left the program
+    Halted,
+}
+
 #[derive(Clone, Default, Debug)]
 pub struct Runtime {
     accumulator: Nibble,
     register: Vec<Nibble>,
     ram: Vec<Command>,
     instruction_counter: usize,
+    layout: MemoryLayout,
+    steps: usize,
+    history: History,
+    breakpoints: BTreeSet<Breakpoint>,
+    /// Whether a breakpoint on the first command was reported before the first step
+    start_break_reported: bool,
+    watchpoints: BTreeSet<Watchpoint>,
+    watchpoint_hits: Vec<WatchpointHit>,
+    last_register_write: Option<(usize, Nibble)>,
+    trace: Option<Trace>,
 }
 
 #[allow(unused)]
@@ -27,16 +72,182 @@ impl Runtime {
         Self {
             accumulator: Nibble::from(0),
             register: vec![Nibble::from(0); register_size],
+            breakpoints: marked_breakpoints(&ram),
             ram,
             instruction_counter: 0,
+            layout: MemoryLayout::Harvard,
+            steps: 0,
+            history: History::default(),
+            start_break_reported: false,
+            watchpoints: BTreeSet::new(),
+            watchpoint_hits: vec![],
+            last_register_write: None,
+            trace: None,
+        }
+    }
+
+    /// Creates a runtime with the commands and the initial registers of a compiled program
+    ///
+    /// Program and registers are separate memories, [`Runtime::from_program_von_neumann`]
+    /// loads both into one.
+    ///
+    /// # Example
+    /// ```
+    /// # use asim::{compile, Runtime};
+    /// let program = compile(".data 0 = 3\n.data 1 = 4\nLDA (0)\nADD (1)").unwrap();
+    /// let mut runtime = Runtime::from_program(program);
+    ///
+    /// while runtime.tick() {}
+    /// assert_eq!(runtime.get_accumulator(), 7);
+    /// ```
+    pub fn from_program(program: Program) -> Self {
+        let mut runtime = Self::new(program.registers.len(), program.commands);
+        runtime.register = program.registers;
+        runtime
+    }
+
+    /// Creates a runtime in which program and data share one memory
+    ///
+    /// The program is encoded into the start of a memory of `memory_size` nibbles,
+    /// the remaining nibbles are set to zero. The fetch stage decodes whatever is
+    /// stored at the instruction counter, and the program ends when the instruction
+    /// counter leaves the memory.
+    ///
+    /// # Example
+    /// ```
+    /// # use asim::{compile, Runtime};
+    /// let commands = compile("LDA #5\nSTA (5)\nLDA #0").unwrap().commands;
+    /// let mut runtime = Runtime::new_von_neumann(16, commands).unwrap();
+    ///
+    /// while runtime.tick() {}
+    /// // `STA (5)` replaced the operand of `LDA #0`
+    /// assert_eq!(runtime.get_accumulator(), 5);
+    /// ```
+    pub fn new_von_neumann(memory_size: usize, ram: Vec<Command>) -> Result<Self, EncodeError> {
+        let mut memory = encode(&ram)?;
+        if memory.len() > memory_size {
+            return Err(EncodeError::MemoryTooSmall {
+                required: memory.len(),
+                available: memory_size,
+            });
         }
+        memory.resize(memory_size, Nibble::from(0));
+
+        Ok(Self {
+            accumulator: Nibble::from(0),
+            register: memory,
+            breakpoints: marked_breakpoints(&ram),
+            ram,
+            instruction_counter: 0,
+            layout: MemoryLayout::VonNeumann,
+            steps: 0,
+            history: History::default(),
+            start_break_reported: false,
+            watchpoints: BTreeSet::new(),
+            watchpoint_hits: vec![],
+            last_register_write: None,
+            trace: None,
+        })
+    }
+
+    /// Creates a runtime with a shared memory from the commands and initial registers
+    /// of a compiled program
+    ///
+    /// Works like [`Runtime::new_von_neumann`], the values set with `.data` are then
+    /// written to their addresses in the same memory. A value other than zero must not
+    /// land inside of the encoded program or behind the end of the memory.
+    ///
+    /// # Example
+    /// ```
+    /// # use asim::{compile, EncodeError, Runtime};
+    /// let program = compile(".data 8 = 3\nLDA (8)\nADD #1").unwrap();
+    /// let mut runtime = Runtime::from_program_von_neumann(16, program).unwrap();
+    ///
+    /// while runtime.tick() {}
+    /// assert_eq!(runtime.get_accumulator(), 4);
+    ///
+    /// let program = compile(".data 1 = 3\nLDA #0").unwrap();
+    /// assert_eq!(
+    ///     Runtime::from_program_von_neumann(16, program).unwrap_err(),
+    ///     EncodeError::DataInProgram { address: 1 }
+    /// );
+    /// ```
+    pub fn from_program_von_neumann(
+        memory_size: usize,
+        program: Program,
+    ) -> Result<Self, EncodeError> {
+        let program_end = program.commands.len() * 2;
+        let mut runtime = Self::new_von_neumann(memory_size, program.commands)?;
+        // Unset registers are zero, so only other values can be told apart from the program
+        for (address, value) in program.registers.into_iter().enumerate() {
+            if value == 0 {
+                continue;
+            }
+            if address < program_end {
+                return Err(EncodeError::DataInProgram { address });
+            }
+            if address >= memory_size {
+                return Err(EncodeError::MemoryTooSmall {
+                    required: address + 1,
+                    available: memory_size,
+                });
+            }
+            runtime.register[address] = value;
+        }
+        Ok(runtime)
+    }
+
+    pub fn get_memory_layout(&self) -> MemoryLayout {
+        self.layout
     }
 
     pub fn get_next_line(&self) -> usize {
-        if self.instruction_counter as usize >= self.ram.len() {
+        if self.instruction_counter >= self.get_program_length() {
             return self.ram.len();
-        } else {
-            return self.ram[self.instruction_counter as usize].line;
+        }
+        // Commands outside of the loaded program have no source line
+        self.ram
+            .get(self.instruction_counter)
+            .map(|command| command.line)
+            .unwrap_or(self.ram.len())
+    }
+
+    /// File of the line returned by [`get_next_line`](Self::get_next_line), an index
+    /// into [`Program::files`](crate::Program::files)
+    pub fn get_next_file(&self) -> usize {
+        self.ram
+            .get(self.instruction_counter)
+            .filter(|_| self.instruction_counter < self.get_program_length())
+            .map_or(0, |command| command.file)
+    }
+
+    /// Number of command slots the instruction counter can address
+    fn get_program_length(&self) -> usize {
+        match self.layout {
+            MemoryLayout::Harvard => self.ram.len(),
+            MemoryLayout::VonNeumann => self.register.len() / 2,
+        }
+    }
+
+    fn fetch(&self) -> Result<Command, RuntimeError> {
+        match self.layout {
+            MemoryLayout::Harvard => Ok(self.ram[self.instruction_counter]),
+            MemoryLayout::VonNeumann => {
+                let address = self.instruction_counter * 2;
+                let opcode = self.register[address].get_value();
+                let operant = self.register[address + 1];
+                let loaded = self.ram.get(self.instruction_counter);
+                let instruction =
+                    Instruction::try_from(opcode).map_err(|_| RuntimeError::InvalidOpcode {
+                        address,
+                        opcode,
+                        line: loaded.map(|command| command.line),
+                        file: loaded.map_or(0, |command| command.file),
+                    })?;
+                let mut command = Command::new(instruction, operant.into(), self.get_next_line());
+                command.file = self.get_next_file();
+                Ok(command)
+            }
         }
     }
 
@@ -56,7 +267,63 @@ impl Runtime {
         self.register[index]
     }
 
-    fn set_register_value<T: Into<Nibble>>(&mut self, index: usize, value: T) {
+    fn read_register(&mut self, command: &Command) -> Result<Nibble, RuntimeError> {
+        let value =
+            self.register
+                .get(command.operant)
+                .copied()
+                .ok_or(RuntimeError::AddressOutOfRange {
+                    command: *command,
+                    address: command.operant,
+                })?;
+        self.check_watchpoints(command, WatchCondition::Read, value, value);
+        Ok(value)
+    }
+
+    fn write_register(&mut self, command: &Command, value: Nibble) -> Result<(), RuntimeError> {
+        let Some(register) = self.register.get_mut(command.operant) else {
+            return Err(RuntimeError::AddressOutOfRange {
+                command: *command,
+                address: command.operant,
+            });
+        };
+        let old = *register;
+        *register = value;
+        self.last_register_write = Some((command.operant, value));
+
+        if let Some(entry) = self.history.last_mut() {
+            entry.register_write = Some((command.operant, old));
+        }
+        self.check_watchpoints(command, WatchCondition::Write, old, value);
+        if old != value {
+            self.check_watchpoints(command, WatchCondition::Change, old, value);
+        }
+        Ok(())
+    }
+
+    fn check_watchpoints(
+        &mut self,
+        command: &Command,
+        condition: WatchCondition,
+        old: Nibble,
+        new: Nibble,
+    ) {
+        let watchpoint = Watchpoint::new(command.operant, condition);
+        if self.watchpoints.contains(&watchpoint) {
+            self.watchpoint_hits.push(WatchpointHit {
+                watchpoint,
+                command: *command,
+                line: command.line,
+                old,
+                new,
+            });
+        }
+    }
+
+    /// Overwrites a register, e.g. to provide the input values of a program
+    ///
+    /// The change is not part of the history. Panics if the register does not exist.
+    pub fn set_register_value<T: Into<Nibble>>(&mut self, index: usize, value: T) {
         self.register[index] = value.into();
     }
 
@@ -68,10 +335,42 @@ impl Runtime {
         self.instruction_counter = value;
     }
 
+    // Jumps may land directly behind the last command to end the program
+    fn jump_to(&mut self, command: &Command, target: usize) -> Result<(), RuntimeError> {
+        if target > self.get_program_length() {
+            return Err(RuntimeError::PcOutOfProgram {
+                command: *command,
+                target,
+            });
+        }
+        self.set_instruction_counter(target);
+        Ok(())
+    }
+
+    // The operand of a branch is a signed offset relative to the branch itself
+    fn branch_if(&mut self, command: &Command, condition: bool) -> Result<(), RuntimeError> {
+        if !condition {
+            self.increase_instruction_counter(1);
+            return Ok(());
+        }
+        let offset = command.get_branch_offset();
+        match self.instruction_counter.checked_add_signed(offset) {
+            Some(target) => self.jump_to(command, target),
+            None => Err(RuntimeError::PcBeforeProgram {
+                command: *command,
+                offset,
+            }),
+        }
+    }
+
     fn get_instruction_counter(&self) -> usize {
         self.instruction_counter
     }
 
+    /// Contents of the registers
+    ///
+    /// In the [`MemoryLayout::VonNeumann`] layout this is the complete memory,
+    /// including the encoded program.
     pub fn get_register(&self) -> &Vec<Nibble> {
         &self.register
     }
@@ -84,63 +383,344 @@ impl Runtime {
         }
     }
 
-    fn execute_command(&mut self) {
-        let command = self.ram[self.instruction_counter as usize];
+    fn execute_command(&mut self) -> Result<Command, RuntimeError> {
+        let command = self.fetch()?;
         match command.instruction {
             Instruction::NOP => (),
             Instruction::LoadFix => self.load_into_accumulator(command.operant),
             Instruction::LoadFromRegister => {
-                self.load_into_accumulator(self.get_register_value(command.operant))
-            }
-            Instruction::SaveToRegister => {
-                self.set_register_value(command.operant, self.get_accumulator())
+                let value = self.read_register(&command)?;
+                self.load_into_accumulator(value)
             }
+            Instruction::SaveToRegister => self.write_register(&command, self.get_accumulator())?,
             Instruction::AddFix => {
                 self.load_into_accumulator(self.get_accumulator() + command.operant.into())
             }
-            Instruction::AddFromRegister => self.load_into_accumulator(
-                self.get_accumulator() + self.get_register_value(command.operant).into(),
-            ),
+            Instruction::AddFromRegister => {
+                let value = self.read_register(&command)?;
+                self.load_into_accumulator(self.get_accumulator() + value)
+            }
             Instruction::SubFix => {
                 self.load_into_accumulator(self.get_accumulator() - command.operant.into())
             }
-            Instruction::SubFromRegister => self.load_into_accumulator(
-                self.get_accumulator() - self.get_register_value(command.operant).into(),
-            ),
+            Instruction::SubFromRegister => {
+                let value = self.read_register(&command)?;
+                self.load_into_accumulator(self.get_accumulator() - value)
+            }
             Instruction::JMP => {
-                self.set_instruction_counter(command.operant);
-                return;
+                self.jump_to(&command, command.operant)?;
+                return Ok(command);
             }
             Instruction::BRZ => {
-                if self.accumulator.is_zero() {
-                    self.increase_instruction_counter(command.operant);
-                } else {
-                    self.increase_instruction_counter(1);
-                }
-                return;
+                self.branch_if(&command, self.accumulator.is_zero())?;
+                return Ok(command);
             }
             Instruction::BRC => {
-                if self.accumulator.has_carry() {
-                    self.increase_instruction_counter(command.operant);
-                } else {
-                    self.increase_instruction_counter(1);
-                }
-                return;
+                self.branch_if(&command, self.accumulator.has_carry())?;
+                return Ok(command);
             }
             Instruction::BRN => {
-                if self.accumulator.has_negative() {
-                    self.increase_instruction_counter(command.operant);
-                } else {
-                    self.increase_instruction_counter(1);
-                }
-                return;
+                self.branch_if(&command, self.accumulator.has_negative())?;
+                return Ok(command);
             }
         }
         self.increase_instruction_counter(1);
+        Ok(command)
     }
 
+    /// Executes the command at the instruction counter
+    ///
+    /// Returns whether the program is still running afterwards. Once the program
+    /// has ended, further calls do nothing and return [`StepOutcome::Halted`].
+    ///
+    /// # Example
+    /// ```
+    /// # use asim::{compile, Runtime, RuntimeError};
+    /// let commands = compile("LDA #1\nSTA (6)").unwrap().commands;
+    /// let mut runtime = Runtime::new(4, commands);
+    ///
+    /// runtime.step().unwrap();
+    /// assert!(matches!(
+    ///     runtime.step(),
+    ///     Err(RuntimeError::AddressOutOfRange { address: 6, .. })
+    /// ));
+    /// ```
+    pub fn step(&mut self) -> Result<StepOutcome, RuntimeError> {
+        self.watchpoint_hits.clear();
+        self.last_register_write = None;
+        if self.get_program_length() == 0 {
+            return Err(RuntimeError::EmptyProgram);
+        }
+        if self.instruction_counter >= self.get_program_length() {
+            return Ok(StepOutcome::Halted);
+        }
+
+        self.history.push(HistoryEntry {
+            step: self.steps,
+            accumulator: self.accumulator,
+            instruction_counter: self.instruction_counter,
+            register_write: None,
+        });
+        let instruction_counter = self.instruction_counter;
+        let command = match self.execute_command() {
+            Ok(command) => command,
+            Err(error) => {
+                self.history.pop();
+                self.watchpoint_hits.clear();
+                self.last_register_write = None;
+                return Err(error);
+            }
+        };
+        self.steps += 1;
+
+        let row = TraceRow {
+            step: self.steps,
+            instruction_counter,
+            command,
+            line: command.line,
+            file: command.file,
+            accumulator: self.accumulator,
+            status: self.get_status_bits(),
+            changed_register: self.last_register_write,
+        };
+        if let Some(trace) = &mut self.trace {
+            trace.push(row);
+        }
+
+        Ok(match self.instruction_counter < self.get_program_length() {
+            true => StepOutcome::Running,
+            false => StepOutcome::Halted,
+        })
+    }
+
+    /// Starts recording a [`TraceRow`] for every following step
+    ///
+    /// A trace that is already being recorded is discarded.
+    pub fn start_trace(&mut self) {
+        self.trace = Some(Trace::new());
+    }
+
+    /// Stops recording and returns the recorded trace
+    pub fn stop_trace(&mut self) -> Option<Trace> {
+        self.trace.take()
+    }
+
+    /// Trace that is currently being recorded
+    pub fn get_trace(&self) -> Option<&Trace> {
+        self.trace.as_ref()
+    }
+
+    /// Number of steps executed since the program started
+    pub fn get_step(&self) -> usize {
+        self.steps
+    }
+
+    /// Reverts the last step
+    ///
+    /// Returns `false` if there is no step left in the history. The trace row and the
+    /// watchpoint hits of the reverted step are removed as well.
+    ///
+    /// # Example
+    /// ```
+    /// # use asim::{compile, Runtime};
+    /// let program = compile("LDA #3\nSTA (1)").unwrap();
+    /// let mut runtime = Runtime::from_program(program);
+    ///
+    /// runtime.step().unwrap();
+    /// runtime.step().unwrap();
+    /// assert!(runtime.step_back());
+    /// assert_eq!(runtime.get_register_value(1), 0);
+    /// assert_eq!(runtime.get_step(), 1);
+    /// ```
+    pub fn step_back(&mut self) -> bool {
+        let Some(entry) = self.history.pop() else {
+            return false;
+        };
+        self.accumulator = entry.accumulator;
+        self.instruction_counter = entry.instruction_counter;
+        if let Some((index, value)) = entry.register_write {
+            self.register[index] = value;
+        }
+        self.steps = entry.step;
+        self.watchpoint_hits.clear();
+        self.last_register_write = None;
+        if let Some(trace) = &mut self.trace {
+            trace.truncate(self.steps);
+        }
+        // Running again from the start stops at its breakpoint again
+        if self.steps == 0 {
+            self.start_break_reported = false;
+        }
+        true
+    }
+
+    /// Reverts steps until [`Runtime::get_step`] equals `step`
+    ///
+    /// Returns `false` and leaves the runtime unchanged if the step lies in the
+    /// future or was already dropped from the history.
+    pub fn rewind_to(&mut self, step: usize) -> bool {
+        match self.history.oldest_step() {
+            _ if step == self.steps => true,
+            Some(oldest) if step >= oldest && step < self.steps => {
+                while self.steps > step {
+                    self.step_back();
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Maximum number of steps that can be reverted
+    pub fn get_history_limit(&self) -> usize {
+        self.history.limit()
+    }
+
+    /// Sets the maximum number of steps that can be reverted, the oldest steps are dropped first
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history.set_limit(limit);
+    }
+
+    /// Adds a breakpoint, returns `false` if it already existed
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> bool {
+        self.breakpoints.insert(breakpoint)
+    }
+
+    /// Removes a breakpoint, returns `false` if it did not exist
+    pub fn remove_breakpoint(&mut self, breakpoint: Breakpoint) -> bool {
+        self.breakpoints.remove(&breakpoint)
+    }
+
+    /// Adds or removes a breakpoint, returns whether it exists afterwards
+    pub fn toggle_breakpoint(&mut self, breakpoint: Breakpoint) -> bool {
+        if self.remove_breakpoint(breakpoint) {
+            return false;
+        }
+        self.add_breakpoint(breakpoint)
+    }
+
+    pub fn has_breakpoint(&self, breakpoint: Breakpoint) -> bool {
+        self.breakpoints.contains(&breakpoint)
+    }
+
+    pub fn get_breakpoints(&self) -> &BTreeSet<Breakpoint> {
+        &self.breakpoints
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /// Adds a watchpoint, returns `false` if it already existed
+    ///
+    /// # Example
+    /// ```
+    /// # use asim::{compile, Runtime, StopReason, WatchCondition};
+    /// let program = compile("LDA #3\nSTA (3)\nSTA (4)").unwrap();
+    /// let mut runtime = Runtime::from_program(program);
+    /// runtime.add_watchpoint(3, WatchCondition::Write);
+    ///
+    /// let Ok(StopReason::Watchpoint(hit)) = runtime.run_until_break(100) else {
+    ///     panic!("Expected a watchpoint");
+    /// };
+    /// assert_eq!(hit.line, 1);
+    /// assert_eq!(hit.new, 3);
+    /// ```
+    pub fn add_watchpoint(&mut self, register: usize, condition: WatchCondition) -> bool {
+        self.watchpoints
+            .insert(Watchpoint::new(register, condition))
+    }
+
+    /// Removes a watchpoint, returns `false` if it did not exist
+    pub fn remove_watchpoint(&mut self, register: usize, condition: WatchCondition) -> bool {
+        self.watchpoints
+            .remove(&Watchpoint::new(register, condition))
+    }
+
+    pub fn get_watchpoints(&self) -> &BTreeSet<Watchpoint> {
+        &self.watchpoints
+    }
+
+    pub fn clear_watchpoints(&mut self) {
+        self.watchpoints.clear();
+    }
+
+    /// Watchpoints triggered by the last step
+    pub fn get_watchpoint_hits(&self) -> &[WatchpointHit] {
+        &self.watchpoint_hits
+    }
+
+    /// Breakpoint in front of the command at the instruction counter, if any
+    fn get_active_breakpoint(&self) -> Option<Breakpoint> {
+        if self.instruction_counter >= self.get_program_length() {
+            return None;
+        }
+        [
+            Breakpoint::Instruction(self.instruction_counter),
+            Breakpoint::Line {
+                file: self.get_next_file(),
+                line: self.get_next_line(),
+            },
+        ]
+        .into_iter()
+        .find(|breakpoint| self.breakpoints.contains(breakpoint))
+    }
+
+    /// Executes commands until a breakpoint or watchpoint, the end of the program or
+    /// `max_steps` is reached
+    ///
+    /// A breakpoint on the first command stops before anything is executed. Otherwise
+    /// at least one command is executed, so a program that stopped at a breakpoint
+    /// continues with the next call.
+    ///
+    /// # Example
+    /// ```
+    /// # use asim::{compile, Breakpoint, Runtime, StopReason};
+    /// let program = compile("LDA #3\nBRK STA (1)\nSUB #1").unwrap();
+    /// let mut runtime = Runtime::from_program(program);
+    ///
+    /// assert_eq!(
+    ///     runtime.run_until_break(100),
+    ///     Ok(StopReason::Breakpoint(Breakpoint::Instruction(1)))
+    /// );
+    /// assert_eq!(runtime.run_until_break(100), Ok(StopReason::Halted));
+    /// ```
+    pub fn run_until_break(&mut self, max_steps: usize) -> Result<StopReason, RuntimeError> {
+        if self.steps == 0 && !self.start_break_reported {
+            self.start_break_reported = true;
+            if let Some(breakpoint) = self.get_active_breakpoint() {
+                return Ok(StopReason::Breakpoint(breakpoint));
+            }
+        }
+        for _ in 0..max_steps {
+            let outcome = self.step()?;
+            if let Some(hit) = self.watchpoint_hits.first() {
+                return Ok(StopReason::Watchpoint(*hit));
+            }
+            if outcome == StepOutcome::Halted {
+                return Ok(StopReason::Halted);
+            }
+            if let Some(breakpoint) = self.get_active_breakpoint() {
+                return Ok(StopReason::Breakpoint(breakpoint));
+            }
+        }
+        Ok(StopReason::StepLimit)
+    }
+
+    /// Executes the command at the instruction counter
+    ///
+    /// Returns `false` once the program has ended or failed. Use [`Runtime::step`]
+    /// to tell both cases apart.
     pub fn tick(&mut self) -> bool {
-        self.execute_command();
-        (self.instruction_counter as usize) < self.ram.len()
+        matches!(self.step(), Ok(StepOutcome::Running))
     }
 }
+
+// Breakpoints for the commands marked with `BRK` in the source code
+fn marked_breakpoints(ram: &[Command]) -> BTreeSet<Breakpoint> {
+    ram.iter()
+        .enumerate()
+        .filter(|(_, command)| command.breakpoint)
+        .map(|(index, _)| Breakpoint::Instruction(index))
+        .collect()
+}
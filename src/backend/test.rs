@@ -1,4 +1,8 @@
+use crate::backend::{
+    Breakpoint, StepOutcome, StopReason, WatchCondition, Watchpoint, WatchpointHit,
+};
 use crate::commands::Instruction;
+use crate::error::{EncodeError, RuntimeError};
 use crate::{backend::Runtime, commands::Command};
 
 #[test]
@@ -38,7 +42,7 @@ fn test_processor_01() {
                 runtime
             );
         }
-        runtime.execute_command();
+        runtime.execute_command().unwrap();
         counter += 1;
     }
 
@@ -84,10 +88,495 @@ fn test_processor_02() {
             );
         }
 
-        runtime.execute_command();
+        runtime.execute_command().unwrap();
         counter += 1;
     }
 
     assert!(runtime.get_register_value(14) == 4, "{:#?}", runtime);
     assert!(runtime.get_register_value(15) == 1, "{:#?}", runtime);
 }
+
+#[test]
+fn test_von_neumann_self_modifying_code() {
+    let ram = vec![
+        Command::new(Instruction::LoadFix, 5, 0),        // 0, 1
+        Command::new(Instruction::SaveToRegister, 5, 1), // 2, 3
+        Command::new(Instruction::LoadFix, 0, 2),        // 4, 5
+    ];
+
+    let mut runtime = Runtime::new_von_neumann(16, ram).unwrap();
+    let mut counter = 0;
+
+    while runtime.tick() {
+        counter += 1;
+        assert!(counter < 100, "Infinite loop fail save; {:#?}", runtime);
+    }
+
+    // The store replaced the operand of `LDA #0` before it was fetched
+    assert!(runtime.get_accumulator() == 5, "{:#?}", runtime);
+    assert!(runtime.get_register_value(5) == 5, "{:#?}", runtime);
+}
+
+#[test]
+fn test_von_neumann_read_code() {
+    let ram = vec![
+        Command::new(Instruction::LoadFromRegister, 2, 0), // 0, 1
+        Command::new(Instruction::AddFromRegister, 3, 1),  // 2, 3
+    ];
+
+    let mut runtime = Runtime::new_von_neumann(4, ram).unwrap();
+    while runtime.tick() {}
+
+    // Opcode 5 of `ADD (3)` plus its operand 3
+    assert!(runtime.get_accumulator() == 8, "{:#?}", runtime);
+}
+
+#[test]
+fn test_von_neumann_from_program() {
+    let mut program = crate::compile(".data 6 = 2\nLDA (6)\nSTA (7)").unwrap();
+    let mut runtime = Runtime::from_program_von_neumann(8, program.clone()).unwrap();
+    while runtime.tick() {}
+    assert!(runtime.get_register_value(7) == 2, "{:#?}", runtime);
+
+    // `.data 15` lies behind the end of a memory with 8 nibbles
+    program.registers[15] = 1.into();
+    assert_eq!(
+        Runtime::from_program_von_neumann(8, program.clone()).unwrap_err(),
+        EncodeError::MemoryTooSmall {
+            required: 16,
+            available: 8
+        }
+    );
+
+    program.registers[2] = 1.into();
+    assert_eq!(
+        Runtime::from_program_von_neumann(8, program).unwrap_err(),
+        EncodeError::DataInProgram { address: 2 }
+    );
+}
+
+#[test]
+fn test_von_neumann_memory_too_small() {
+    let ram = vec![Command::new(Instruction::LoadFix, 1, 0); 3];
+
+    assert_eq!(
+        Runtime::new_von_neumann(4, ram).unwrap_err(),
+        EncodeError::MemoryTooSmall {
+            required: 6,
+            available: 4
+        }
+    );
+}
+
+#[test]
+fn test_step_empty_program() {
+    let mut runtime = Runtime::new(16, vec![]);
+
+    assert_eq!(runtime.step(), Err(RuntimeError::EmptyProgram));
+    assert!(!runtime.tick());
+}
+
+#[test]
+fn test_step_address_out_of_range() {
+    let command = Command::new(Instruction::SaveToRegister, 20, 1);
+    let ram = vec![Command::new(Instruction::LoadFix, 1, 0), command];
+    let mut runtime = Runtime::new(16, ram);
+
+    assert_eq!(runtime.step(), Ok(StepOutcome::Running));
+
+    let error = runtime.step().unwrap_err();
+    assert_eq!(
+        error,
+        RuntimeError::AddressOutOfRange {
+            command,
+            address: 20
+        }
+    );
+    assert_eq!(error.line(), Some(1));
+    // A failed command does not change the state
+    assert_eq!(runtime.instruction_counter, 1);
+}
+
+#[test]
+fn test_step_branch_out_of_program() {
+    let command = Command::new(Instruction::BRZ, 5, 1);
+    let ram = vec![Command::new(Instruction::LoadFix, 0, 0), command];
+    let mut runtime = Runtime::new(16, ram);
+
+    runtime.step().unwrap();
+    assert_eq!(
+        runtime.step(),
+        Err(RuntimeError::PcOutOfProgram { command, target: 6 })
+    );
+}
+
+#[test]
+fn test_step_branch_backwards() {
+    let ram = vec![
+        Command::new(Instruction::LoadFix, 3, 0),        // 0
+        Command::new(Instruction::SubFix, 1, 1),         // 1
+        Command::new(Instruction::BRZ, 2, 2),            // 2
+        Command::new(Instruction::BRC, 0b1110, 3),       // 3, back to 1
+        Command::new(Instruction::SaveToRegister, 0, 4), // 4
+    ];
+    let mut runtime = Runtime::new(16, ram);
+
+    assert_eq!(runtime.run_until_break(100), Ok(StopReason::Halted));
+    assert_eq!(runtime.get_step(), 10);
+    assert_eq!(runtime.get_register_value(0), 0);
+}
+
+#[test]
+fn test_step_branch_before_program() {
+    let command = Command::new(Instruction::BRZ, 0b1110, 1);
+    let ram = vec![Command::new(Instruction::LoadFix, 0, 0), command];
+    let mut runtime = Runtime::new(16, ram);
+
+    runtime.step().unwrap();
+    assert_eq!(
+        runtime.step(),
+        Err(RuntimeError::PcBeforeProgram {
+            command,
+            offset: -2
+        })
+    );
+    assert_eq!(runtime.instruction_counter, 1);
+}
+
+#[test]
+fn test_step_jump_to_end() {
+    let ram = vec![
+        Command::new(Instruction::JMP, 2, 0),
+        Command::new(Instruction::LoadFix, 1, 1),
+    ];
+    let mut runtime = Runtime::new(16, ram);
+
+    assert_eq!(runtime.step(), Ok(StepOutcome::Halted));
+    assert_eq!(runtime.step(), Ok(StepOutcome::Halted));
+    assert!(runtime.get_accumulator() == 0, "{:#?}", runtime);
+}
+
+#[test]
+fn test_step_invalid_opcode() {
+    let ram = vec![
+        Command::new(Instruction::LoadFix, 12, 0),       // 0, 1
+        Command::new(Instruction::SaveToRegister, 4, 1), // 2, 3
+    ];
+    let mut runtime = Runtime::new_von_neumann(6, ram).unwrap();
+
+    runtime.step().unwrap();
+    runtime.step().unwrap();
+    assert_eq!(
+        runtime.step(),
+        Err(RuntimeError::InvalidOpcode {
+            address: 4,
+            opcode: 12,
+            line: None,
+            file: 0,
+        })
+    );
+
+    // The overwritten command still names its line and file
+    let mut ram = vec![
+        Command::new(Instruction::LoadFix, 12, 0),       // 0, 1
+        Command::new(Instruction::SaveToRegister, 4, 1), // 2, 3
+        Command::new(Instruction::NOP, 0, 2),            // 4, 5
+    ];
+    ram[2].file = 1;
+    let mut runtime = Runtime::new_von_neumann(6, ram).unwrap();
+
+    runtime.step().unwrap();
+    runtime.step().unwrap();
+    let error = runtime.step().unwrap_err();
+    assert_eq!(
+        error,
+        RuntimeError::InvalidOpcode {
+            address: 4,
+            opcode: 12,
+            line: Some(2),
+            file: 1,
+        }
+    );
+    assert_eq!(error.line(), Some(2));
+    assert_eq!(error.file(), 1);
+    assert_eq!(error.command(), None);
+}
+
+#[test]
+fn test_step_back() {
+    let ram = vec![
+        Command::new(Instruction::LoadFix, 3, 0),
+        Command::new(Instruction::SaveToRegister, 1, 1),
+        Command::new(Instruction::SubFix, 1, 2),
+        Command::new(Instruction::BRZ, 2, 3),
+        Command::new(Instruction::JMP, 1, 4),
+    ];
+    let mut runtime = Runtime::new(16, ram);
+
+    while runtime.tick() {}
+    assert!(runtime.get_register_value(1) == 1, "{:#?}", runtime);
+    let steps = runtime.get_step();
+
+    assert!(runtime.step_back());
+    assert_eq!(runtime.get_step(), steps - 1);
+    assert_eq!(runtime.instruction_counter, 3);
+
+    assert!(runtime.rewind_to(2));
+    assert_eq!(runtime.instruction_counter, 2);
+    assert!(runtime.get_accumulator() == 3, "{:#?}", runtime);
+    assert!(runtime.get_register_value(1) == 3, "{:#?}", runtime);
+
+    assert!(runtime.rewind_to(0));
+    assert!(runtime.get_register_value(1) == 0, "{:#?}", runtime);
+    assert!(!runtime.step_back());
+    assert!(!runtime.rewind_to(1));
+
+    // Replaying after a rewind reaches the same state again
+    while runtime.tick() {}
+    assert_eq!(runtime.get_step(), steps);
+    assert!(runtime.get_register_value(1) == 1, "{:#?}", runtime);
+}
+
+#[test]
+fn test_step_back_trace_and_watchpoints() {
+    let ram = vec![
+        Command::new(Instruction::LoadFix, 3, 0),
+        Command::new(Instruction::SaveToRegister, 1, 1),
+        Command::new(Instruction::AddFix, 1, 2),
+    ];
+    let mut runtime = Runtime::new(16, ram);
+    runtime.add_watchpoint(1, WatchCondition::Write);
+    runtime.start_trace();
+
+    runtime.step().unwrap();
+    runtime.step().unwrap();
+    assert_eq!(runtime.get_watchpoint_hits().len(), 1);
+    assert_eq!(runtime.get_trace().unwrap().get_rows().len(), 2);
+
+    // The reverted step leaves no row or hit behind
+    assert!(runtime.step_back());
+    assert!(runtime.get_watchpoint_hits().is_empty());
+    let steps: Vec<usize> = runtime.get_trace().unwrap().get_rows().iter().map(|row| row.step).collect();
+    assert_eq!(steps, [1]);
+
+    while runtime.tick() {}
+    let steps: Vec<usize> = runtime.get_trace().unwrap().get_rows().iter().map(|row| row.step).collect();
+    assert_eq!(steps, [1, 2, 3]);
+
+    assert!(runtime.rewind_to(0));
+    assert!(runtime.get_trace().unwrap().get_rows().is_empty());
+}
+
+#[test]
+fn test_history_limit() {
+    let ram = vec![Command::new(Instruction::AddFix, 1, 0); 6];
+    let mut runtime = Runtime::new(16, ram);
+    runtime.set_history_limit(2);
+
+    while runtime.tick() {}
+
+    assert!(!runtime.rewind_to(3));
+    assert!(runtime.rewind_to(4));
+    assert!(runtime.get_accumulator() == 4, "{:#?}", runtime);
+    assert!(!runtime.step_back());
+}
+
+#[test]
+fn test_failed_step_is_not_recorded() {
+    let ram = vec![
+        Command::new(Instruction::LoadFix, 1, 0),
+        Command::new(Instruction::SaveToRegister, 20, 1),
+    ];
+    let mut runtime = Runtime::new(16, ram);
+
+    runtime.step().unwrap();
+    assert!(runtime.step().is_err());
+    assert_eq!(runtime.get_step(), 1);
+    assert!(runtime.step_back());
+    assert_eq!(runtime.instruction_counter, 0);
+}
+
+#[test]
+fn test_run_until_break() {
+    let ram = vec![
+        Command::new(Instruction::LoadFix, 3, 0),
+        Command::new(Instruction::SubFix, 1, 1),
+        Command::new(Instruction::BRZ, 2, 2),
+        Command::new(Instruction::JMP, 1, 3),
+        Command::new(Instruction::NOP, 0, 4),
+    ];
+    let mut runtime = Runtime::new(16, ram);
+    runtime.add_breakpoint(Breakpoint::Line { file: 0, line: 3 });
+
+    for remaining in [2, 1] {
+        assert_eq!(
+            runtime.run_until_break(100),
+            Ok(StopReason::Breakpoint(Breakpoint::Line { file: 0, line: 3 }))
+        );
+        assert!(runtime.get_accumulator() == remaining, "{:#?}", runtime);
+    }
+    assert_eq!(runtime.run_until_break(100), Ok(StopReason::Halted));
+    assert_eq!(runtime.run_until_break(100), Ok(StopReason::Halted));
+}
+
+#[test]
+fn test_run_until_break_step_limit() {
+    let ram = vec![Command::new(Instruction::JMP, 0, 0)];
+    let mut runtime = Runtime::new(16, ram);
+
+    assert_eq!(runtime.run_until_break(10), Ok(StopReason::StepLimit));
+    assert_eq!(runtime.get_step(), 10);
+
+    assert!(runtime.toggle_breakpoint(Breakpoint::Instruction(0)));
+    assert_eq!(
+        runtime.run_until_break(10),
+        Ok(StopReason::Breakpoint(Breakpoint::Instruction(0)))
+    );
+    assert!(!runtime.toggle_breakpoint(Breakpoint::Instruction(0)));
+    assert!(runtime.get_breakpoints().is_empty());
+}
+
+#[test]
+fn test_run_until_break_first_command() {
+    let mut command = Command::new(Instruction::LoadFix, 3, 0);
+    command.breakpoint = true;
+    let ram = vec![command, Command::new(Instruction::AddFix, 1, 1)];
+    let mut runtime = Runtime::new(16, ram);
+
+    // The breakpoint stops before the first command runs, only once
+    let breakpoint = Ok(StopReason::Breakpoint(Breakpoint::Instruction(0)));
+    assert_eq!(runtime.run_until_break(100), breakpoint);
+    assert_eq!(runtime.get_step(), 0);
+    assert_eq!(runtime.run_until_break(100), Ok(StopReason::Halted));
+    assert_eq!(runtime.get_accumulator(), 4);
+
+    assert!(runtime.rewind_to(0));
+    assert_eq!(runtime.run_until_break(100), breakpoint);
+}
+
+#[test]
+fn test_marked_breakpoints() {
+    let mut command = Command::new(Instruction::AddFix, 1, 1);
+    command.breakpoint = true;
+    let ram = vec![Command::new(Instruction::LoadFix, 3, 0), command];
+
+    let runtime = Runtime::new(16, ram);
+    assert!(runtime.has_breakpoint(Breakpoint::Instruction(1)));
+}
+
+#[test]
+fn test_watchpoints() {
+    let ram = vec![
+        Command::new(Instruction::LoadFix, 3, 0),
+        Command::new(Instruction::SaveToRegister, 3, 1),
+        Command::new(Instruction::SaveToRegister, 3, 2),
+        Command::new(Instruction::AddFromRegister, 3, 3),
+        Command::new(Instruction::SaveToRegister, 3, 4),
+    ];
+    let mut runtime = Runtime::new(16, ram.clone());
+    runtime.add_watchpoint(3, WatchCondition::Change);
+    runtime.add_watchpoint(3, WatchCondition::Read);
+
+    let expected_hits = [
+        (WatchCondition::Change, ram[1], 0, 3),
+        (WatchCondition::Read, ram[3], 3, 3),
+        (WatchCondition::Change, ram[4], 3, 6),
+    ];
+    for (condition, command, old, new) in expected_hits {
+        let Ok(StopReason::Watchpoint(hit)) = runtime.run_until_break(100) else {
+            panic!("Expected watchpoint; {:#?}", runtime);
+        };
+        assert_eq!(hit.watchpoint, Watchpoint::new(3, condition));
+        assert_eq!(hit.command, command);
+        assert_eq!(hit.line, command.line);
+        assert!(hit.old == old && hit.new == new, "{:#?}", hit);
+    }
+    assert_eq!(runtime.run_until_break(100), Ok(StopReason::Halted));
+}
+
+#[test]
+fn test_watchpoint_write() {
+    let ram = vec![
+        Command::new(Instruction::SaveToRegister, 3, 0),
+        Command::new(Instruction::SaveToRegister, 4, 1),
+    ];
+    let mut runtime = Runtime::new(16, ram);
+    runtime.add_watchpoint(3, WatchCondition::Write);
+    runtime.add_watchpoint(4, WatchCondition::Change);
+
+    // Writing the same value only triggers `Write`
+    assert!(matches!(
+        runtime.run_until_break(100),
+        Ok(StopReason::Watchpoint(WatchpointHit { line: 0, .. }))
+    ));
+    assert_eq!(runtime.run_until_break(100), Ok(StopReason::Halted));
+    assert!(runtime.get_watchpoint_hits().is_empty());
+}
+
+#[test]
+fn test_trace() {
+    let ram = vec![
+        Command::new(Instruction::LoadFix, 1, 0),
+        Command::new(Instruction::SubFix, 1, 1),
+        Command::new(Instruction::SaveToRegister, 2, 2),
+    ];
+    let mut runtime = Runtime::new(16, ram.clone());
+
+    runtime.step().unwrap();
+    runtime.start_trace();
+    while runtime.tick() {}
+
+    let trace = runtime.stop_trace().unwrap();
+    let rows = trace.get_rows();
+    assert_eq!(rows.len(), 2);
+
+    assert_eq!(rows[0].step, 2);
+    assert_eq!(rows[0].instruction_counter, 1);
+    assert_eq!(rows[0].command, ram[1]);
+    assert!(rows[0].accumulator == 0, "{:#?}", rows[0]);
+    assert!(rows[0].status.zero && rows[0].status.carry, "{:#?}", rows[0]);
+    assert_eq!(rows[0].changed_register, None);
+
+    assert_eq!(rows[1].line, 2);
+    assert!(
+        matches!(rows[1].changed_register, Some((2, value)) if value == 0),
+        "{:#?}",
+        rows[1]
+    );
+    assert!(runtime.get_trace().is_none());
+}
+
+#[test]
+fn test_included_file_lines() {
+    use crate::{compile_with_loader, CompileOptions};
+    use std::collections::HashMap;
+
+    let mut files = HashMap::new();
+    files.insert(
+        "main.asm".to_string(),
+        "LDA #1\n.include \"lib.asm\"\nSTA (1)".to_string(),
+    );
+    files.insert("lib.asm".to_string(), "ADD #1\nADD #2".to_string());
+    let program = compile_with_loader("main.asm", &files, &CompileOptions::default()).unwrap();
+
+    // The line of the `.include` contains no command, line 1 of `lib.asm` does
+    let mut runtime = Runtime::from_program(program);
+    runtime.add_breakpoint(Breakpoint::Line { file: 0, line: 1 });
+    runtime.add_breakpoint(Breakpoint::Line { file: 1, line: 1 });
+    runtime.start_trace();
+    assert_eq!(
+        runtime.run_until_break(100),
+        Ok(StopReason::Breakpoint(Breakpoint::Line { file: 1, line: 1 }))
+    );
+    assert_eq!((runtime.get_next_file(), runtime.get_next_line()), (1, 1));
+    assert_eq!(runtime.run_until_break(100), Ok(StopReason::Halted));
+
+    let rows: Vec<(usize, usize)> = runtime
+        .get_trace()
+        .unwrap()
+        .get_rows()
+        .iter()
+        .map(|row| (row.file, row.line))
+        .collect();
+    assert_eq!(rows, [(0, 0), (1, 0), (1, 1), (0, 2)]);
+}
@@ -0,0 +1,156 @@
+use std::io::Write;
+
+use super::StatusBits;
+use crate::commands::Command;
+use crate::nibble::Nibble;
+
+/// State of the runtime after a single step
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TraceRow {
+    /// Number of steps executed including this one
+    pub step: usize,
+    /// Instruction counter of the executed command
+    pub instruction_counter: usize,
+    pub command: Command,
+    pub line: usize,
+    /// Index into [`Program::files`](crate::Program::files)
+    pub file: usize,
+    pub accumulator: Nibble,
+    pub status: StatusBits,
+    /// Register written by the command and its new value
+    pub changed_register: Option<(usize, Nibble)>,
+}
+
+/// Rows recorded by [`Runtime::start_trace`](super::Runtime::start_trace), one per step
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Trace {
+    rows: Vec<TraceRow>,
+}
+
+impl Trace {
+    pub fn new() -> Self {
+        Self { rows: vec![] }
+    }
+
+    pub(crate) fn push(&mut self, row: TraceRow) {
+        self.rows.push(row);
+    }
+
+    /// Removes the rows of the steps after `step`
+    pub(crate) fn truncate(&mut self, step: usize) {
+        self.rows.retain(|row| row.step <= step);
+    }
+
+    pub fn get_rows(&self) -> &[TraceRow] {
+        &self.rows
+    }
+
+    /// Writes the rows as CSV with a header line, flags are written as `0` or `1`
+    ///
+    /// `file` is the index into [`Program::files`](crate::Program::files) of the line.
+    ///
+    /// # Example
+    /// ```
+    /// # use asim::{compile, Runtime};
+    /// let program = compile("LDA #3\nSTA (1)").unwrap();
+    /// let mut runtime = Runtime::from_program(program);
+    /// runtime.start_trace();
+    /// while runtime.tick() {}
+    ///
+    /// let trace = runtime.stop_trace().unwrap();
+    /// assert_eq!(
+    ///     trace.to_csv(),
+    ///     "step,instruction_counter,command,file,line,accumulator,carry,negative,zero,register,register_value
+    /// 1,0,LDA #3,0,0,3,0,0,0,,
+    /// 2,1,STA (1),0,1,3,0,0,0,1,3
+    /// "
+    /// );
+    /// ```
+    pub fn write_csv<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writeln!(
+            writer,
+            "step,instruction_counter,command,file,line,accumulator,carry,negative,zero,register,register_value"
+        )?;
+        for row in &self.rows {
+            let (register, register_value) = match row.changed_register {
+                Some((index, value)) => (index.to_string(), value.to_string()),
+                None => (String::new(), String::new()),
+            };
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{},{},{},{}",
+                row.step,
+                row.instruction_counter,
+                row.command,
+                row.file,
+                row.line,
+                row.accumulator,
+                row.status.carry as u8,
+                row.status.negative as u8,
+                row.status.zero as u8,
+                register,
+                register_value
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes one JSON object per row and line
+    ///
+    /// # Example
+    /// ```
+    /// # use asim::{compile, Runtime};
+    /// let program = compile("STA (1)").unwrap();
+    /// let mut runtime = Runtime::from_program(program);
+    /// runtime.start_trace();
+    /// while runtime.tick() {}
+    ///
+    /// let trace = runtime.stop_trace().unwrap();
+    /// assert_eq!(
+    ///     trace.to_json_lines(),
+    ///     r#"{"step":1,"instruction_counter":0,"command":"STA (1)","file":0,"line":0,"accumulator":0,"carry":false,"negative":false,"zero":true,"changed_register":{"index":1,"value":0}}
+    /// "#
+    /// );
+    /// ```
+    pub fn write_json_lines<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        for row in &self.rows {
+            let changed_register = match row.changed_register {
+                Some((index, value)) => format!("{{\"index\":{},\"value\":{}}}", index, value),
+                None => String::from("null"),
+            };
+            writeln!(
+                writer,
+                "{{\"step\":{},\"instruction_counter\":{},\"command\":\"{}\",\"file\":{},\"line\":{},\"accumulator\":{},\"carry\":{},\"negative\":{},\"zero\":{},\"changed_register\":{}}}",
+                row.step,
+                row.instruction_counter,
+                escape_json(&row.command.to_string()),
+                row.file,
+                row.line,
+                row.accumulator,
+                row.status.carry,
+                row.status.negative,
+                row.status.zero,
+                changed_register
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut buffer: Vec<u8> = vec![];
+        self.write_csv(&mut buffer)
+            .expect("Writing into a vector can not fail");
+        String::from_utf8(buffer).expect("The trace is valid UTF-8")
+    }
+
+    pub fn to_json_lines(&self) -> String {
+        let mut buffer: Vec<u8> = vec![];
+        self.write_json_lines(&mut buffer)
+            .expect("Writing into a vector can not fail");
+        String::from_utf8(buffer).expect("The trace is valid UTF-8")
+    }
+}
+
+fn escape_json(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,41 @@
+use crate::commands::Command;
+use crate::nibble::Nibble;
+
+/// Access to a register that triggers a [`Watchpoint`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum WatchCondition {
+    /// The register is read by `LDA`, `ADD` or `SUB`
+    Read,
+    /// The register is written by `STA`
+    Write,
+    /// The register is written by `STA` and its value changes
+    Change,
+}
+
+/// Stops [`Runtime::run_until_break`](super::Runtime::run_until_break) when a register is accessed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Watchpoint {
+    pub register: usize,
+    pub condition: WatchCondition,
+}
+
+impl Watchpoint {
+    pub fn new(register: usize, condition: WatchCondition) -> Self {
+        Self {
+            register,
+            condition,
+        }
+    }
+}
+
+/// Register access that triggered a [`Watchpoint`]
+///
+/// For reads `old` and `new` are both the value that was read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WatchpointHit {
+    pub watchpoint: Watchpoint,
+    pub command: Command,
+    pub line: usize,
+    pub old: Nibble,
+    pub new: Nibble,
+}
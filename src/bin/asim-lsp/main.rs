@@ -0,0 +1,38 @@
+use std::io::{self, BufReader};
+use std::process::ExitCode;
+
+use server::{parse_error, Server};
+use transport::{read_message, write_message};
+
+mod json;
+mod server;
+mod transport;
+
+/// Language server for the assembly files, speaks the protocol on stdin and stdout
+fn main() -> ExitCode {
+    let mut input = BufReader::new(io::stdin().lock());
+    let mut output = io::stdout().lock();
+    let mut server = Server::new();
+
+    loop {
+        let responses = match read_message(&mut input) {
+            Ok(Some(Ok(message))) => server.handle(&message),
+            // The client is told about the broken message, the server keeps running
+            Ok(Some(Err(message))) => vec![parse_error(&message)],
+            Ok(None) => return ExitCode::from(1),
+            Err(err) => {
+                eprintln!("asim-lsp: {}", err);
+                return ExitCode::from(1);
+            }
+        };
+        for response in responses {
+            if let Err(err) = write_message(&mut output, &response) {
+                eprintln!("asim-lsp: {}", err);
+                return ExitCode::from(1);
+            }
+        }
+        if let Some(code) = server.exit_code() {
+            return ExitCode::from(code);
+        }
+    }
+}
@@ -0,0 +1,926 @@
+use std::collections::HashMap;
+use std::io;
+
+use asim::{
+    compile_with_loader, find_symbols, get_instruction_attribute, AnalysisSession, CompileOptions,
+    ParseError, Severity, SourceLoader, SymbolKind, SymbolOccurrence, SymbolTable, TextEdit,
+    TextPosition, MNEMONICS,
+};
+
+use crate::json::{object, Json};
+
+// Error codes of the protocol
+const PARSE_ERROR: i32 = -32700;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_REQUEST: i32 = -32600;
+const INVALID_PARAMS: i32 = -32602;
+
+// Kinds of completion items
+const COMPLETION_FUNCTION: usize = 3;
+const COMPLETION_KEYWORD: usize = 14;
+const COMPLETION_REFERENCE: usize = 18;
+const COMPLETION_CONSTANT: usize = 21;
+
+type RequestResult = Result<Json, (i32, String)>;
+
+/// Open file, the text is used instead of the file on disk
+struct Document {
+    uri: String,
+    /// Text of the editor, analysed again after every change
+    session: AnalysisSession,
+}
+
+pub struct Server {
+    /// Open documents by path
+    documents: HashMap<String, Document>,
+    shutdown: bool,
+    exit_code: Option<u8>,
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Self {
+            documents: HashMap::new(),
+            shutdown: false,
+            exit_code: None,
+        }
+    }
+
+    /// Set after the `exit` notification, `0` if the client asked for a shutdown before
+    pub fn exit_code(&self) -> Option<u8> {
+        self.exit_code
+    }
+
+    /// Handles a request or notification and returns the messages for the client
+    pub fn handle(&mut self, message: &Json) -> Vec<Json> {
+        let Some(method) = message.get("method").as_str() else {
+            // Responses to requests of the server, none are sent
+            return vec![];
+        };
+        let params = message.get("params");
+        let id = message.get("id");
+        if *id == Json::Null {
+            return self.notification(method, params);
+        }
+
+        let result = match self.shutdown && method != "exit" {
+            true => Err((INVALID_REQUEST, String::from("The server was shut down"))),
+            false => self.request(method, params),
+        };
+        let response = match result {
+            Ok(result) => object([
+                ("jsonrpc", "2.0".into()),
+                ("id", id.clone()),
+                ("result", result),
+            ]),
+            Err((code, message)) => error_response(id, code, &message),
+        };
+        vec![response]
+    }
+
+    fn request(&mut self, method: &str, params: &Json) -> RequestResult {
+        match method {
+            "initialize" => Ok(initialize()),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Json::Null)
+            }
+            "textDocument/hover" => self.hover(params),
+            "textDocument/definition" => self.definition(params),
+            "textDocument/references" => self.references(params),
+            "textDocument/completion" => self.completion(params),
+            "textDocument/codeAction" => Ok(code_actions(params)),
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method `{}`", method))),
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Json) -> Vec<Json> {
+        let document = params.get("textDocument");
+        let uri = document.get("uri").as_str().unwrap_or("");
+        match method {
+            "exit" => {
+                self.exit_code = Some(if self.shutdown { 0 } else { 1 });
+                vec![]
+            }
+            "textDocument/didOpen" => {
+                let text = document.get("text").as_str().unwrap_or("");
+                self.documents.insert(
+                    uri_to_path(uri),
+                    Document {
+                        uri: uri.to_string(),
+                        session: AnalysisSession::new(text),
+                    },
+                );
+                self.publish_all()
+            }
+            "textDocument/didChange" => {
+                // Changes with a range edit the text, the others replace it
+                if let Some(document) = self.documents.get_mut(&uri_to_path(uri)) {
+                    for change in params.get("contentChanges").as_array() {
+                        let Some(text) = change.get("text").as_str() else {
+                            continue;
+                        };
+                        match text_edit(document.session.text(), change.get("range"), text) {
+                            Some(edit) => document.session.edit(&edit),
+                            None => document.session = AnalysisSession::new(text),
+                        }
+                    }
+                }
+                self.publish_all()
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri_to_path(uri));
+                let mut messages = vec![publish(uri, vec![])];
+                messages.extend(self.publish_all());
+                messages
+            }
+            _ => vec![],
+        }
+    }
+
+    // Files include each other, so every open file is checked again
+    fn publish_all(&mut self) -> Vec<Json> {
+        let mut paths: Vec<String> = self.documents.keys().cloned().collect();
+        paths.sort();
+        paths
+            .into_iter()
+            .map(|path| {
+                let diagnostics = self.diagnostics(&path);
+                publish(&self.documents[&path].uri, diagnostics)
+            })
+            .collect()
+    }
+
+    // The session of the document is used unless other files are included
+    fn diagnostics(&mut self, path: &str) -> Vec<Json> {
+        let report = match self.documents.get_mut(path) {
+            Some(document) if !document.session.has_includes() => {
+                document.session.diagnostics().clone()
+            }
+            _ => {
+                let loader = Documents(&self.documents);
+                match compile_with_loader(path, &loader, &CompileOptions::default()) {
+                    Ok(program) => program.diagnostics,
+                    Err(report) => report,
+                }
+            }
+        };
+        report
+            .warnings
+            .iter()
+            .chain(&report.errors)
+            .filter_map(diagnostic)
+            .collect()
+    }
+
+    fn hover(&self, params: &Json) -> RequestResult {
+        let (path, line, column) = self.position(params)?;
+        let text = self.line_text(&path, line);
+        let (start, end) = word_at(&text, column);
+        let word = &text[start..end];
+        let range = range(&text, line, start, end);
+
+        if let Some(documentation) = mnemonic_documentation(word) {
+            return Ok(hover_result(documentation, range));
+        }
+        let table = self.symbols(&path);
+        let Some(symbol) = table.find_at(0, line, column) else {
+            return Ok(Json::Null);
+        };
+        let Some(definition) = table.definitions_of(symbol).next() else {
+            return Ok(Json::Null);
+        };
+        let kind = match definition.kind {
+            SymbolKind::Label => "label",
+            SymbolKind::Constant => "constant",
+            SymbolKind::Macro => "macro",
+        };
+        let path = table
+            .files
+            .get(definition.file)
+            .cloned()
+            .unwrap_or_default();
+        let source = self.line_text(&path, definition.line);
+        let documentation = format!(
+            "{} `{}`\n\n```asm\n{}\n```",
+            kind,
+            symbol.name,
+            source.trim()
+        );
+        Ok(hover_result(documentation, range))
+    }
+
+    fn definition(&self, params: &Json) -> RequestResult {
+        let (path, line, column) = self.position(params)?;
+        let table = self.symbols(&path);
+        let Some(symbol) = table.find_at(0, line, column) else {
+            return Ok(Json::Null);
+        };
+        let locations = table
+            .definitions_of(symbol)
+            .map(|definition| self.location(&table, definition))
+            .collect();
+        Ok(Json::Array(locations))
+    }
+
+    fn references(&self, params: &Json) -> RequestResult {
+        let (path, line, column) = self.position(params)?;
+        let include_declaration = params
+            .get("context")
+            .get("includeDeclaration")
+            .as_bool()
+            .unwrap_or(true);
+        let table = self.symbols(&path);
+        let Some(symbol) = table.find_at(0, line, column) else {
+            return Ok(Json::Null);
+        };
+        let locations = table
+            .references_of(symbol)
+            .filter(|reference| include_declaration || !reference.definition)
+            .map(|reference| self.location(&table, reference))
+            .collect();
+        Ok(Json::Array(locations))
+    }
+
+    // Instructions at the start of a line, names of labels and constants behind them
+    fn completion(&self, params: &Json) -> RequestResult {
+        let (path, line, column) = self.position(params)?;
+        let text = self.line_text(&path, line);
+        let prefix = &text[..column];
+        if prefix.contains("//") {
+            return Ok(Json::Array(vec![]));
+        }
+        let statement = prefix.rsplit_once(':').map_or(prefix, |(_, rest)| rest);
+        let statement = statement.trim_start();
+        let statement = match statement.get(..4) {
+            Some(marker) if marker.eq_ignore_ascii_case("BRK ") => statement[4..].trim_start(),
+            _ => statement,
+        };
+        let instruction_position = !statement.contains(char::is_whitespace);
+
+        let table = self.symbols(&path);
+        let mut items = vec![];
+        if instruction_position {
+            for mnemonic in MNEMONICS {
+                items.push(completion_item(
+                    mnemonic,
+                    COMPLETION_KEYWORD,
+                    mnemonic_summary(mnemonic),
+                ));
+            }
+        }
+        let mut seen = vec![];
+        for symbol in table.definitions() {
+            let kind = match symbol.kind {
+                SymbolKind::Macro if instruction_position => COMPLETION_FUNCTION,
+                SymbolKind::Label if !instruction_position => COMPLETION_REFERENCE,
+                SymbolKind::Constant if !instruction_position => COMPLETION_CONSTANT,
+                _ => continue,
+            };
+            if seen.contains(&symbol.name) {
+                continue;
+            }
+            seen.push(symbol.name.clone());
+            let detail = match symbol.kind {
+                SymbolKind::Label => "label",
+                SymbolKind::Constant => "constant",
+                SymbolKind::Macro => "macro",
+            };
+            items.push(completion_item(&symbol.name, kind, detail));
+        }
+        Ok(Json::Array(items))
+    }
+
+    fn symbols(&self, path: &str) -> SymbolTable {
+        find_symbols(path, &Documents(&self.documents))
+    }
+
+    fn location(&self, table: &SymbolTable, symbol: &SymbolOccurrence) -> Json {
+        let path = table.files.get(symbol.file).cloned().unwrap_or_default();
+        let text = self.line_text(&path, symbol.line);
+        object([
+            ("uri", self.uri_of(&path).into()),
+            ("range", range(&text, symbol.line, symbol.start, symbol.end)),
+        ])
+    }
+
+    fn uri_of(&self, path: &str) -> String {
+        match self.documents.get(path) {
+            Some(document) => document.uri.clone(),
+            None => path_to_uri(path),
+        }
+    }
+
+    fn line_text(&self, path: &str, line: usize) -> String {
+        Documents(&self.documents)
+            .load(path)
+            .ok()
+            .and_then(|text| text.lines().nth(line).map(str::to_string))
+            .unwrap_or_default()
+    }
+
+    // Path, line and byte column of `textDocument` and `position`
+    fn position(&self, params: &Json) -> Result<(String, usize, usize), (i32, String)> {
+        let uri = params.get("textDocument").get("uri").as_str();
+        let line = params.get("position").get("line").as_usize();
+        let character = params.get("position").get("character").as_usize();
+        let (Some(uri), Some(line), Some(character)) = (uri, line, character) else {
+            return Err((
+                INVALID_PARAMS,
+                String::from("Missing text document position"),
+            ));
+        };
+        let path = uri_to_path(uri);
+        let column = byte_column(&self.line_text(&path, line), character);
+        Ok((path, line, column))
+    }
+}
+
+/// Reads open documents from memory and all other files from the disk
+struct Documents<'a>(&'a HashMap<String, Document>);
+
+impl SourceLoader for Documents<'_> {
+    fn load(&self, path: &str) -> io::Result<String> {
+        match self.0.get(path) {
+            Some(document) => Ok(document.session.text().to_string()),
+            None => std::fs::read_to_string(path),
+        }
+    }
+}
+
+/// Response to a message that could not be parsed, its id is unknown
+pub fn parse_error(message: &str) -> Json {
+    error_response(&Json::Null, PARSE_ERROR, message)
+}
+
+fn error_response(id: &Json, code: i32, message: &str) -> Json {
+    object([
+        ("jsonrpc", "2.0".into()),
+        ("id", id.clone()),
+        (
+            "error",
+            object([
+                ("code", Json::Number(code as f64)),
+                ("message", message.into()),
+            ]),
+        ),
+    ])
+}
+
+fn initialize() -> Json {
+    object([
+        (
+            "capabilities",
+            object([
+                ("textDocumentSync", 2.into()),
+                ("hoverProvider", true.into()),
+                ("definitionProvider", true.into()),
+                ("referencesProvider", true.into()),
+                ("codeActionProvider", true.into()),
+                (
+                    "completionProvider",
+                    object([("triggerCharacters", vec!["#".into(), "(".into()].into())]),
+                ),
+            ]),
+        ),
+        (
+            "serverInfo",
+            object([
+                ("name", "asim-lsp".into()),
+                ("version", env!("CARGO_PKG_VERSION").into()),
+            ]),
+        ),
+    ])
+}
+
+fn publish(uri: &str, diagnostics: Vec<Json>) -> Json {
+    object([
+        ("jsonrpc", "2.0".into()),
+        ("method", "textDocument/publishDiagnostics".into()),
+        (
+            "params",
+            object([("uri", uri.into()), ("diagnostics", diagnostics.into())]),
+        ),
+    ])
+}
+
+// Errors in included files are shown at the macro call of the open file, if any
+fn diagnostic(error: &ParseError) -> Option<Json> {
+    let (line, start, end) = match &error.invocation {
+        _ if error.line.file == 0 => (&error.line, error.start, error.end),
+        Some(invocation) if invocation.line.file == 0 => {
+            (&invocation.line, invocation.start, invocation.end)
+        }
+        _ => return None,
+    };
+    let mut message = error.kind.to_string();
+    if let Some(invocation) = &error.invocation {
+        message += &format!(" (in the expansion of macro `{}`)", invocation.name);
+    }
+    let severity = match error.severity {
+        Severity::Error => 1,
+        Severity::Warning => 2,
+    };
+
+    let mut diagnostic = vec![
+        (
+            "range".to_string(),
+            range(&line.value, line.number, start, end),
+        ),
+        ("severity".to_string(), severity.into()),
+        ("source".to_string(), "asim".into()),
+        ("message".to_string(), message.into()),
+    ];
+    // The code action turns the suggestion into a quick fix
+    if let (Some(suggestion), 0) = (&error.suggestion, error.line.file) {
+        diagnostic.push((
+            "data".to_string(),
+            object([("suggestion", suggestion.as_str().into())]),
+        ));
+    }
+    Some(Json::Object(diagnostic))
+}
+
+fn code_actions(params: &Json) -> Json {
+    let uri = params.get("textDocument").get("uri");
+    let actions = params
+        .get("context")
+        .get("diagnostics")
+        .as_array()
+        .iter()
+        .filter_map(|diagnostic| {
+            let suggestion = diagnostic.get("data").get("suggestion").as_str()?;
+            let edit = object([
+                ("range", diagnostic.get("range").clone()),
+                ("newText", suggestion.into()),
+            ]);
+            let changes = Json::Object(vec![(uri.as_str()?.to_string(), vec![edit].into())]);
+            Some(object([
+                ("title", format!("Replace with `{}`", suggestion).into()),
+                ("kind", "quickfix".into()),
+                ("diagnostics", vec![diagnostic.clone()].into()),
+                ("isPreferred", true.into()),
+                ("edit", object([("changes", changes)])),
+            ]))
+        })
+        .collect();
+    Json::Array(actions)
+}
+
+fn hover_result(documentation: String, range: Json) -> Json {
+    object([
+        (
+            "contents",
+            object([("kind", "markdown".into()), ("value", documentation.into())]),
+        ),
+        ("range", range),
+    ])
+}
+
+fn completion_item(label: &str, kind: usize, detail: &str) -> Json {
+    object([
+        ("label", label.into()),
+        ("kind", kind.into()),
+        ("detail", detail.into()),
+    ])
+}
+
+fn mnemonic_summary(mnemonic: &str) -> &'static str {
+    match mnemonic {
+        "NOP" => "No operation",
+        "LDA" => "Load the accumulator",
+        "STA" => "Store the accumulator in a register",
+        "ADD" => "Add to the accumulator",
+        "SUB" => "Subtract from the accumulator",
+        "JMP" => "Continue at another command",
+        "BRZ" => "Branch if the zero bit is set",
+        "BRC" => "Branch if the carry bit is set",
+        "BRN" => "Branch if the negative bit is set",
+        _ => "",
+    }
+}
+
+fn mnemonic_description(mnemonic: &str) -> &'static str {
+    match mnemonic {
+        "NOP" => {
+            "Does nothing. `NOP #n` stores `n` in the machine code, other operands are ignored."
+        }
+        "LDA" => "Loads the value `n` or the contents of the register `n` into the accumulator.",
+        "STA" => "Stores the accumulator in the register `n`.",
+        "ADD" => "Adds the value `n` or the contents of the register `n` to the accumulator.",
+        "SUB" => {
+            "Subtracts the value `n` or the contents of the register `n` from the accumulator."
+        }
+        "JMP" => "Continues with the command `n` or the command behind the label.",
+        "BRZ" | "BRC" | "BRN" => {
+            "Adds the signed offset `n` to the instruction pointer if the bit is set, \
+             a label is converted to its offset."
+        }
+        _ => "",
+    }
+}
+
+// Markdown with the description and the operands allowed by the instruction attributes
+fn mnemonic_documentation(word: &str) -> Option<String> {
+    let mnemonic = word.to_uppercase();
+    let attributes = get_instruction_attribute(&mnemonic)?;
+    let mut forms = vec![];
+    if attributes.allow_no_operant() {
+        forms.push(mnemonic.clone());
+    }
+    if attributes.allow_fixed_number() {
+        // Jump targets are written without `#` like in the table of the readme
+        match mnemonic.as_str() {
+            "JMP" => forms.push(format!("{} n", mnemonic)),
+            _ => forms.push(format!("{} #n", mnemonic)),
+        }
+    }
+    if attributes.allow_address() {
+        forms.push(format!("{} (n)", mnemonic));
+    }
+    if attributes.allow_label() {
+        forms.push(format!("{} label", mnemonic));
+    }
+    Some(format!(
+        "**{}** — {}\n\n{}\n\n```asm\n{}\n```",
+        mnemonic,
+        mnemonic_summary(&mnemonic),
+        mnemonic_description(&mnemonic),
+        forms.join("\n")
+    ))
+}
+
+// Byte range of the name around the column
+fn word_at(text: &str, column: usize) -> (usize, usize) {
+    let is_name = |c: char| c == '_' || c.is_alphanumeric();
+    let start = text[..column]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_name(*c))
+        .last()
+        .map_or(column, |(index, _)| index);
+    let end = text[column..]
+        .char_indices()
+        .find(|(_, c)| !is_name(*c))
+        .map_or(text.len(), |(index, _)| column + index);
+    (start, end)
+}
+
+// Positions of the protocol count UTF-16 code units
+fn range(text: &str, line: usize, start: usize, end: usize) -> Json {
+    let position = |column: usize| {
+        object([
+            ("line", line.into()),
+            ("character", utf16_column(text, column).into()),
+        ])
+    };
+    object([("start", position(start)), ("end", position(end))])
+}
+
+// Edit of a change with a range, `None` for a change of the whole text
+fn text_edit(text: &str, range: &Json, new_text: &str) -> Option<TextEdit> {
+    let position = |position: &Json| {
+        let line = position.get("line").as_usize()?;
+        let character = position.get("character").as_usize()?;
+        let line_text = text.split('\n').nth(line).unwrap_or("");
+        Some(TextPosition::new(line, byte_column(line_text, character)))
+    };
+    Some(TextEdit::new(
+        position(range.get("start"))?,
+        position(range.get("end"))?,
+        new_text,
+    ))
+}
+
+fn utf16_column(text: &str, column: usize) -> usize {
+    text.get(..column.min(text.len()))
+        .map_or(column, |prefix| prefix.encode_utf16().count())
+}
+
+fn byte_column(text: &str, character: usize) -> usize {
+    let mut units = 0;
+    for (index, c) in text.char_indices() {
+        if units >= character {
+            return index;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+fn uri_to_path(uri: &str) -> String {
+    let Some(path) = uri.strip_prefix("file://") else {
+        return uri.to_string();
+    };
+    let path = percent_decode(path);
+    // `file:///C:/…` on Windows
+    match path.as_bytes() {
+        [b'/', drive, b':', ..] if drive.is_ascii_alphabetic() => path[1..].to_string(),
+        _ => path,
+    }
+}
+
+fn path_to_uri(path: &str) -> String {
+    let mut uri = String::from("file://");
+    if !path.starts_with('/') {
+        uri.push('/');
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                uri.push(byte as char)
+            }
+            b'\\' => uri.push('/'),
+            byte => uri += &format!("%{:02X}", byte),
+        }
+    }
+    uri
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = bytes
+            .get(index + 1..index + 3)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u8::from_str_radix(digits, 16).ok());
+        match (bytes[index], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{byte_column, path_to_uri, uri_to_path, utf16_column, Server};
+    use crate::json::{object, Json};
+
+    // Not on the disk, so the server only knows the opened text
+    const URI: &str = "file:///nonexistent/main.asm";
+
+    fn open(server: &mut Server, text: &str) -> Vec<Json> {
+        server.handle(&object([
+            ("jsonrpc", "2.0".into()),
+            ("method", "textDocument/didOpen".into()),
+            (
+                "params",
+                object([(
+                    "textDocument",
+                    object([("uri", URI.into()), ("text", text.into())]),
+                )]),
+            ),
+        ]))
+    }
+
+    fn change(server: &mut Server, changes: Vec<Json>) -> Vec<Json> {
+        server.handle(&object([
+            ("jsonrpc", "2.0".into()),
+            ("method", "textDocument/didChange".into()),
+            (
+                "params",
+                object([
+                    ("textDocument", object([("uri", URI.into())])),
+                    ("contentChanges", changes.into()),
+                ]),
+            ),
+        ]))
+    }
+
+    fn request(server: &mut Server, method: &str, params: Json) -> Json {
+        let mut responses = server.handle(&object([
+            ("jsonrpc", "2.0".into()),
+            ("id", 7.into()),
+            ("method", method.into()),
+            ("params", params),
+        ]));
+        assert_eq!(responses.len(), 1);
+        let response = responses.remove(0);
+        assert_eq!(*response.get("id"), Json::from(7));
+        response
+    }
+
+    fn position(line: usize, character: usize) -> Json {
+        object([
+            ("textDocument", object([("uri", URI.into())])),
+            (
+                "position",
+                object([("line", line.into()), ("character", character.into())]),
+            ),
+        ])
+    }
+
+    fn range(line: usize, start: usize, end: usize) -> Json {
+        let position =
+            |character: usize| object([("line", line.into()), ("character", character.into())]);
+        object([("start", position(start)), ("end", position(end))])
+    }
+
+    #[test]
+    fn test_columns() {
+        let text = "ä😀x";
+        assert_eq!(utf16_column(text, 0), 0);
+        assert_eq!(utf16_column(text, 2), 1);
+        assert_eq!(utf16_column(text, 6), 3);
+        assert_eq!(utf16_column(text, 7), 4);
+        assert_eq!(utf16_column(text, 20), 4);
+
+        assert_eq!(byte_column(text, 0), 0);
+        assert_eq!(byte_column(text, 1), 2);
+        assert_eq!(byte_column(text, 3), 6);
+        assert_eq!(byte_column(text, 4), 7);
+        assert_eq!(byte_column(text, 20), 7);
+        // Inside of the surrogate pair
+        assert_eq!(byte_column(text, 2), 6);
+    }
+
+    #[test]
+    fn test_uri_path() {
+        assert_eq!(uri_to_path("file:///tmp/a%20b.asm"), "/tmp/a b.asm");
+        assert_eq!(uri_to_path("file:///tmp/%C3%A4.asm"), "/tmp/ä.asm");
+        assert_eq!(uri_to_path("file:///C:/x.asm"), "C:/x.asm");
+        assert_eq!(uri_to_path("untitled:1"), "untitled:1");
+
+        assert_eq!(path_to_uri("/tmp/a b.asm"), "file:///tmp/a%20b.asm");
+        assert_eq!(path_to_uri("/tmp/ä.asm"), "file:///tmp/%C3%A4.asm");
+        assert_eq!(path_to_uri("C:\\x.asm"), "file:///C:/x.asm");
+        for path in ["/tmp/a b.asm", "/tmp/ä%.asm", "C:/x.asm"] {
+            assert_eq!(uri_to_path(&path_to_uri(path)), path);
+        }
+    }
+
+    #[test]
+    fn test_publish_diagnostics() {
+        let mut server = Server::new();
+        let messages = open(&mut server, "LDA #1\nJMP strat\n");
+        assert_eq!(messages.len(), 1);
+        let params = messages[0].get("params");
+        assert_eq!(
+            *messages[0].get("method"),
+            Json::from("textDocument/publishDiagnostics")
+        );
+        assert_eq!(*params.get("uri"), Json::from(URI));
+        let diagnostics = params.get("diagnostics").as_array();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(*diagnostics[0].get("range"), range(1, 4, 9));
+        assert_eq!(*diagnostics[0].get("severity"), Json::from(1));
+
+        let messages = open(&mut server, "LDA #1\n");
+        assert_eq!(messages[0].get("params").get("diagnostics").as_array(), []);
+    }
+
+    #[test]
+    fn test_incremental_change() {
+        let mut server = Server::new();
+        open(&mut server, "start: LDA #1\nJMP strat\n");
+        let diagnostics = |messages: &[Json]| {
+            messages[0]
+                .get("params")
+                .get("diagnostics")
+                .as_array()
+                .len()
+        };
+        let edit = |start: (usize, usize), end: (usize, usize), text: &str| {
+            let position = |(line, character): (usize, usize)| {
+                object([("line", line.into()), ("character", character.into())])
+            };
+            object([
+                (
+                    "range",
+                    object([("start", position(start)), ("end", position(end))]),
+                ),
+                ("text", text.into()),
+            ])
+        };
+
+        let messages = change(&mut server, vec![edit((1, 4), (1, 9), "start")]);
+        assert_eq!(diagnostics(&messages), 0);
+
+        // Characters are counted in UTF-16 code units, changes are applied in order
+        let messages = change(
+            &mut server,
+            vec![
+                edit((0, 0), (0, 0), "// ä😀\n"),
+                edit((0, 6), (0, 6), "\nSTA (16)"),
+            ],
+        );
+        assert_eq!(diagnostics(&messages), 1);
+        let response = request(&mut server, "textDocument/hover", position(3, 6));
+        let value = response.get("result").get("contents").get("value");
+        assert!(value.as_str().unwrap().starts_with("label `start`"));
+
+        // A change without a range replaces the text
+        let text = object([("text", "JMP strat".into())]);
+        let messages = change(&mut server, vec![text]);
+        assert_eq!(
+            *messages[0].get("params").get("diagnostics").as_array()[0].get("range"),
+            range(0, 4, 9)
+        );
+    }
+
+    #[test]
+    fn test_hover() {
+        let mut server = Server::new();
+        open(&mut server, "start: lda #1\n    JMP start\n");
+
+        let response = request(&mut server, "textDocument/hover", position(0, 8));
+        let contents = response.get("result").get("contents");
+        assert_eq!(*contents.get("kind"), Json::from("markdown"));
+        assert!(contents
+            .get("value")
+            .as_str()
+            .unwrap()
+            .starts_with("**LDA**"));
+        assert_eq!(*response.get("result").get("range"), range(0, 7, 10));
+
+        let response = request(&mut server, "textDocument/hover", position(1, 10));
+        let value = response.get("result").get("contents").get("value");
+        assert!(value.as_str().unwrap().starts_with("label `start`"));
+
+        let response = request(&mut server, "textDocument/hover", position(0, 12));
+        assert_eq!(*response.get("result"), Json::Null);
+    }
+
+    #[test]
+    fn test_definition_references() {
+        let mut server = Server::new();
+        open(&mut server, "start: LDA #1\n    JMP start\n    BRZ start\n");
+
+        let response = request(&mut server, "textDocument/definition", position(2, 9));
+        let location = object([("uri", URI.into()), ("range", range(0, 0, 5))]);
+        assert_eq!(*response.get("result"), Json::Array(vec![location]));
+
+        let mut params = position(0, 2);
+        if let Json::Object(entries) = &mut params {
+            let context = object([("includeDeclaration", false.into())]);
+            entries.push((String::from("context"), context));
+        }
+        let response = request(&mut server, "textDocument/references", params);
+        let locations: Vec<Json> = [1, 2]
+            .into_iter()
+            .map(|line| object([("uri", URI.into()), ("range", range(line, 8, 13))]))
+            .collect();
+        assert_eq!(*response.get("result"), Json::Array(locations));
+
+        let response = request(&mut server, "textDocument/references", position(0, 2));
+        assert_eq!(response.get("result").as_array().len(), 3);
+    }
+
+    #[test]
+    fn test_completion() {
+        let mut server = Server::new();
+        open(
+            &mut server,
+            ".macro m\n    NOP\n.endm\nstart: LDA #1\n    JMP \n",
+        );
+        let labels = |response: &Json| -> Vec<String> {
+            response
+                .get("result")
+                .as_array()
+                .iter()
+                .filter_map(|item| item.get("label").as_str().map(str::to_string))
+                .collect()
+        };
+
+        let response = request(&mut server, "textDocument/completion", position(3, 8));
+        let items = labels(&response);
+        assert!(items.contains(&String::from("LDA")));
+        assert!(items.contains(&String::from("m")));
+        assert!(!items.contains(&String::from("start")));
+
+        let response = request(&mut server, "textDocument/completion", position(4, 8));
+        assert_eq!(labels(&response), ["start"]);
+    }
+
+    #[test]
+    fn test_shutdown() {
+        let mut server = Server::new();
+        let response = request(&mut server, "initialize", Json::Object(vec![]));
+        assert!(response.get("result").get("capabilities") != &Json::Null);
+        let response = request(&mut server, "unknown", Json::Null);
+        assert_eq!(*response.get("error").get("code"), Json::Number(-32601.0));
+
+        let response = request(&mut server, "shutdown", Json::Null);
+        assert_eq!(*response.get("result"), Json::Null);
+        let response = request(&mut server, "textDocument/hover", position(0, 0));
+        assert_eq!(*response.get("error").get("code"), Json::Number(-32600.0));
+        assert_eq!(server.exit_code(), None);
+
+        let exit = object([("jsonrpc", "2.0".into()), ("method", "exit".into())]);
+        assert!(server.handle(&exit).is_empty());
+        assert_eq!(server.exit_code(), Some(0));
+
+        let mut server = Server::new();
+        server.handle(&exit);
+        assert_eq!(server.exit_code(), Some(1));
+    }
+}
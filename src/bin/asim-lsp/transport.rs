@@ -0,0 +1,78 @@
+use std::io::{self, BufRead, Write};
+
+use crate::json::Json;
+
+/// Reads the next message, `None` at the end of the input
+///
+/// Messages start with a `Content-Length` header followed by an empty line.
+/// A body that is no valid JSON is returned as `Err` with the reason, the
+/// following messages can still be read.
+pub fn read_message(input: &mut impl BufRead) -> io::Result<Option<Result<Json, String>>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let Some(length) = length else {
+        return Ok(Some(Err(String::from("missing Content-Length header"))));
+    };
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    let message = String::from_utf8(body)
+        .map_err(|_| String::from("message is not UTF-8"))
+        .and_then(|body| Json::parse(&body));
+    Ok(Some(message))
+}
+
+pub fn write_message(output: &mut impl Write, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_message, write_message};
+    use crate::json::{object, Json};
+    use std::io::Cursor;
+
+    #[test]
+    fn test_read_message() {
+        let input = "Content-Length: 2\r\nContent-Type: x\r\n\r\n{}\
+                     Content-Length: 3\r\n\r\n{x}\
+                     content-length: 4\r\n\r\nnull";
+        let mut input = Cursor::new(input.as_bytes());
+
+        let message = read_message(&mut input).unwrap().unwrap();
+        assert_eq!(message, Ok(Json::Object(vec![])));
+        // The invalid body is skipped, the next message is read as usual
+        assert!(read_message(&mut input).unwrap().unwrap().is_err());
+        assert_eq!(read_message(&mut input).unwrap().unwrap(), Ok(Json::Null));
+        assert_eq!(read_message(&mut input).unwrap(), None);
+
+        let mut input = Cursor::new("Content-Length: 10\r\n\r\n{}".as_bytes());
+        assert!(read_message(&mut input).is_err());
+    }
+
+    #[test]
+    fn test_write_message() {
+        let mut output = vec![];
+        write_message(&mut output, &object([("text", "ä".into())])).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Content-Length: 13\r\n\r\n{\"text\":\"ä\"}"
+        );
+    }
+}
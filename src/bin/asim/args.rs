@@ -0,0 +1,160 @@
+use std::path::PathBuf;
+
+pub const USAGE: &str = "Usage: asim <command> <file> [options]
+
+Commands:
+    check    Compile the file and report all errors
+    run      Execute the file and print the final state
+    trace    Execute the file and print the state after every step
+    debug    Step through the file in an interactive debugger
+    fmt      Print the file in the common style
+
+Options:
+    -r, --registers <n>        Number of registers (default: 16)
+    -m, --max-steps <n>        Maximum number of steps per run (default: 1000)
+    -s, --set <index>=<value>  Initial value of a register, may be repeated
+    -f, --format <format>      Output of `trace`: table, csv or json (default: table)
+    -w, --write                Let `fmt` overwrite the file instead of printing it
+    -c, --check                Let `fmt` fail if the file is not formatted,
+                               files with CRLF line endings always fail
+    -h, --help                 Print this help
+";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Subcommand {
+    Check,
+    Run,
+    Trace,
+    Debug,
+    Fmt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TraceFormat {
+    Table,
+    Csv,
+    Json,
+}
+
+#[derive(Debug)]
+pub struct Arguments {
+    pub subcommand: Subcommand,
+    pub file: PathBuf,
+    pub registers: usize,
+    pub max_steps: usize,
+    pub initial_registers: Vec<(usize, u8)>,
+    pub format: TraceFormat,
+    pub write: bool,
+    pub check: bool,
+}
+
+#[derive(Debug)]
+pub enum ArgumentError {
+    Help,
+    Invalid(String),
+}
+
+impl Arguments {
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Self, ArgumentError> {
+        let subcommand = match args.next().as_deref() {
+            Some("check") => Subcommand::Check,
+            Some("run") => Subcommand::Run,
+            Some("trace") => Subcommand::Trace,
+            Some("debug") => Subcommand::Debug,
+            Some("fmt") => Subcommand::Fmt,
+            Some("-h" | "--help") | None => return Err(ArgumentError::Help),
+            Some(other) => return Err(invalid(format!("Unknown command `{}`", other))),
+        };
+
+        let mut arguments = Self {
+            subcommand,
+            file: PathBuf::new(),
+            registers: 16,
+            max_steps: 1000,
+            initial_registers: vec![],
+            format: TraceFormat::Table,
+            write: false,
+            check: false,
+        };
+        let mut file: Option<PathBuf> = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => return Err(ArgumentError::Help),
+                "-r" | "--registers" => {
+                    arguments.registers = parse_number(&arg, args.next())?;
+                }
+                "-m" | "--max-steps" => {
+                    arguments.max_steps = parse_number(&arg, args.next())?;
+                }
+                "-s" | "--set" => {
+                    let value = args.next().ok_or(missing_value(&arg))?;
+                    arguments.initial_registers.push(parse_assignment(&value)?);
+                }
+                "-f" | "--format" => {
+                    arguments.format = match args.next().as_deref() {
+                        Some("table") => TraceFormat::Table,
+                        Some("csv") => TraceFormat::Csv,
+                        Some("json") => TraceFormat::Json,
+                        Some(other) => return Err(invalid(format!("Unknown format `{}`", other))),
+                        None => return Err(missing_value(&arg)),
+                    };
+                }
+                "-w" | "--write" => arguments.write = true,
+                "-c" | "--check" => arguments.check = true,
+                option if option.starts_with('-') => {
+                    return Err(invalid(format!("Unknown option `{}`", option)));
+                }
+                path => {
+                    if file.replace(PathBuf::from(path)).is_some() {
+                        return Err(invalid(String::from("Only one file can be given")));
+                    }
+                }
+            }
+        }
+
+        arguments.file = file.ok_or(invalid(String::from("Missing file")))?;
+        if let Some((index, _)) = arguments
+            .initial_registers
+            .iter()
+            .find(|(index, _)| *index >= arguments.registers)
+        {
+            return Err(invalid(format!(
+                "Register {} does not exist, there are only {} registers",
+                index, arguments.registers
+            )));
+        }
+        Ok(arguments)
+    }
+}
+
+fn parse_number(option: &str, value: Option<String>) -> Result<usize, ArgumentError> {
+    let value = value.ok_or(missing_value(option))?;
+    value
+        .parse()
+        .map_err(|_| invalid(format!("`{}` expects a number, got `{}`", option, value)))
+}
+
+// Parses `<index>=<value>` with a value that fits into a nibble
+fn parse_assignment(value: &str) -> Result<(usize, u8), ArgumentError> {
+    let error = || invalid(format!("Expected `<index>=<value>`, got `{}`", value));
+
+    let (index, nibble) = value.split_once('=').ok_or_else(error)?;
+    let index: usize = index.trim().parse().map_err(|_| error())?;
+    let nibble: u8 = nibble.trim().parse().map_err(|_| error())?;
+    if nibble > 15 {
+        return Err(invalid(format!(
+            "The value {} of register {} does not fit into a nibble",
+            nibble, index
+        )));
+    }
+    Ok((index, nibble))
+}
+
+fn missing_value(option: &str) -> ArgumentError {
+    invalid(format!("Missing value for `{}`", option))
+}
+
+fn invalid(message: String) -> ArgumentError {
+    ArgumentError::Invalid(message)
+}
@@ -0,0 +1,452 @@
+use std::io::{self, Write};
+
+use asim::{Breakpoint, Command, Nibble, Program, Runtime, StepOutcome, StopReason};
+
+use crate::args::Arguments;
+use crate::terminal::{Key, Terminal};
+
+const HELP: &str =
+    "s step  p back  r run  x reset  b breakpoint  j/k line  e edit registers  q quit";
+const EDIT_HELP: &str = "arrows select  0-9 a-f set  +/- change  enter/esc/q done";
+
+// Width of the pane with the accumulator, flags and registers
+const STATE_WIDTH: usize = 30;
+const GRID_COLUMNS: usize = 4;
+
+const RESET: &str = "\x1B[0m";
+const REVERSE: &str = "\x1B[7m";
+const CHANGED: &str = "\x1B[1;33m";
+const BREAKPOINT: &str = "\x1B[31m";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Normal,
+    Edit { register: usize },
+}
+
+struct Debugger<'a> {
+    arguments: &'a Arguments,
+    source: Vec<String>,
+    program: Program,
+    runtime: Runtime,
+    /// Registers before the last action, changed cells are highlighted
+    previous_register: Vec<Nibble>,
+    halted: bool,
+    /// Selected line of the first file, breakpoints are toggled here
+    cursor: usize,
+    scroll: usize,
+    mode: Mode,
+    message: String,
+}
+
+/// Runs the interactive debugger until the user quits
+pub fn debug(arguments: &Arguments, text: &str, program: Program) -> io::Result<()> {
+    let terminal = Terminal::enter()?;
+    let mut debugger = Debugger::new(arguments, text, program);
+
+    loop {
+        debugger.render(&terminal)?;
+        let key = terminal.read_key()?;
+        if !debugger.handle_key(key) {
+            return Ok(());
+        }
+    }
+}
+
+impl<'a> Debugger<'a> {
+    fn new(arguments: &'a Arguments, text: &str, program: Program) -> Self {
+        let runtime = create_runtime(arguments, &program);
+        Self {
+            arguments,
+            source: text.lines().map(|line| line.replace('\t', "    ")).collect(),
+            cursor: program
+                .commands
+                .iter()
+                .find(|command| command.file == 0)
+                .map_or(0, |command| command.line),
+            previous_register: runtime.get_register().clone(),
+            program,
+            runtime,
+            halted: false,
+            scroll: 0,
+            mode: Mode::Normal,
+            message: String::new(),
+        }
+    }
+
+    /// Returns `false` if the debugger should be closed
+    fn handle_key(&mut self, key: Key) -> bool {
+        if key == Key::Interrupt {
+            return false;
+        }
+        match self.mode {
+            Mode::Normal => return self.handle_normal_key(key),
+            Mode::Edit { register } => self.handle_edit_key(key, register),
+        }
+        true
+    }
+
+    fn handle_normal_key(&mut self, key: Key) -> bool {
+        self.message.clear();
+        match key {
+            Key::Char('q') => return false,
+            Key::Char('s' | ' ') | Key::Enter => self.step(),
+            Key::Char('p') => self.step_back(),
+            Key::Char('r') => self.run(),
+            Key::Char('x') => self.reset(),
+            Key::Char('b') => self.toggle_breakpoint(),
+            Key::Char('k') | Key::Up => self.cursor = self.cursor.saturating_sub(1),
+            Key::Char('j') | Key::Down => {
+                self.cursor = (self.cursor + 1).min(self.source.len().saturating_sub(1));
+            }
+            Key::Char('e') => match self.runtime.get_register().is_empty() {
+                true => self.message = String::from("There are no registers to edit"),
+                false => self.mode = Mode::Edit { register: 0 },
+            },
+            _ => {}
+        }
+        true
+    }
+
+    fn handle_edit_key(&mut self, key: Key, register: usize) {
+        let count = self.runtime.get_register().len();
+        let selected = match key {
+            // `e` is the hexadecimal digit 14 here
+            Key::Enter | Key::Escape | Key::Char('q') => {
+                self.mode = Mode::Normal;
+                return;
+            }
+            Key::Left | Key::Char('h') => register.checked_sub(1),
+            Key::Right | Key::Char('l') => Some(register + 1),
+            Key::Up | Key::Char('k') => register.checked_sub(GRID_COLUMNS),
+            Key::Down | Key::Char('j') => Some(register + GRID_COLUMNS),
+            Key::Char('+') => {
+                let value = self.runtime.get_register_value(register) + Nibble::from(1u8);
+                self.runtime.set_register_value(register, value);
+                None
+            }
+            Key::Char('-') => {
+                let value = self.runtime.get_register_value(register) - Nibble::from(1u8);
+                self.runtime.set_register_value(register, value);
+                None
+            }
+            Key::Char(digit) => {
+                if let Some(value) = digit.to_digit(16) {
+                    self.runtime.set_register_value(register, value as u8);
+                }
+                None
+            }
+            _ => None,
+        };
+        if let Some(selected) = selected.filter(|&selected| selected < count) {
+            self.mode = Mode::Edit { register: selected };
+        }
+    }
+
+    fn step(&mut self) {
+        if self.halted {
+            self.message = String::from("The program has halted, press x to reset");
+            return;
+        }
+        self.previous_register = self.runtime.get_register().clone();
+        match self.runtime.step() {
+            Ok(StepOutcome::Running) => {}
+            Ok(StepOutcome::Halted) => self.halt(),
+            Err(err) => self.message = format!("Error: {}", err),
+        }
+        self.follow();
+    }
+
+    fn step_back(&mut self) {
+        self.previous_register = self.runtime.get_register().clone();
+        if self.runtime.step_back() {
+            self.halted = false;
+        } else {
+            self.message = String::from("There is no earlier step in the history");
+        }
+        self.follow();
+    }
+
+    fn run(&mut self) {
+        if self.halted {
+            self.message = String::from("The program has halted, press x to reset");
+            return;
+        }
+        self.previous_register = self.runtime.get_register().clone();
+        match self.runtime.run_until_break(self.arguments.max_steps) {
+            Ok(StopReason::Breakpoint(_)) => {
+                let line = self.runtime.get_next_line() + 1;
+                self.message = match self.runtime.get_next_file() {
+                    0 => format!("Stopped at the breakpoint in line {}", line),
+                    file => format!(
+                        "Stopped at the breakpoint in {}:{}",
+                        self.program.files[file], line
+                    ),
+                };
+            }
+            Ok(StopReason::Watchpoint(hit)) => {
+                self.message = format!("Register {} was accessed", hit.watchpoint.register);
+            }
+            Ok(StopReason::Halted) => self.halt(),
+            Ok(StopReason::StepLimit) => {
+                self.message = format!("Stopped after {} steps", self.arguments.max_steps);
+            }
+            Err(err) => self.message = format!("Error: {}", err),
+        }
+        self.follow();
+    }
+
+    fn halt(&mut self) {
+        self.halted = true;
+        self.message = format!("The program halted after {} steps", self.runtime.get_step());
+    }
+
+    // Restarts the program, breakpoints set by the user are kept
+    fn reset(&mut self) {
+        let breakpoints = self.runtime.get_breakpoints().clone();
+        self.runtime = create_runtime(self.arguments, &self.program);
+        self.runtime.clear_breakpoints();
+        for breakpoint in breakpoints {
+            self.runtime.add_breakpoint(breakpoint);
+        }
+        self.previous_register = self.runtime.get_register().clone();
+        self.halted = false;
+        self.message = String::from("The program was reset");
+        self.follow();
+    }
+
+    fn toggle_breakpoint(&mut self) {
+        let line = self.cursor;
+        if self.lines_commands(line).next().is_none() {
+            self.message = format!("Line {} contains no command", line + 1);
+            return;
+        }
+        if self.has_breakpoint(line) {
+            // Removes the `BRK` markers of the line as well
+            self.runtime.remove_breakpoint(Breakpoint::Line { file: 0, line });
+            let indices: Vec<usize> = self.lines_commands(line).map(|(index, _)| index).collect();
+            for index in indices {
+                self.runtime.remove_breakpoint(Breakpoint::Instruction(index));
+            }
+        } else {
+            self.runtime.add_breakpoint(Breakpoint::Line { file: 0, line });
+        }
+    }
+
+    fn has_breakpoint(&self, line: usize) -> bool {
+        self.runtime.has_breakpoint(Breakpoint::Line { file: 0, line })
+            || self
+                .lines_commands(line)
+                .any(|(index, _)| self.runtime.has_breakpoint(Breakpoint::Instruction(index)))
+    }
+
+    // Commands of a line of the first file, only this file is shown
+    fn lines_commands(&self, line: usize) -> impl Iterator<Item = (usize, &Command)> {
+        self.program
+            .commands
+            .iter()
+            .enumerate()
+            .filter(move |(_, command)| command.file == 0 && command.line == line)
+    }
+
+    // No line is highlighted while a command of an included file runs
+    fn current_line(&self) -> Option<usize> {
+        if self.halted || self.runtime.get_next_file() != 0 {
+            return None;
+        }
+        Some(self.runtime.get_next_line())
+    }
+
+    // Moves the cursor to the line that is executed next
+    fn follow(&mut self) {
+        if let Some(line) = self.current_line() {
+            if line < self.source.len() {
+                self.cursor = line;
+            }
+        }
+    }
+
+    fn render(&mut self, terminal: &Terminal) -> io::Result<()> {
+        let (rows, cols) = terminal.size();
+        let height = rows.saturating_sub(2).max(1);
+        let source_width = cols.saturating_sub(STATE_WIDTH + 1).max(10);
+
+        if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        } else if self.cursor >= self.scroll + height {
+            self.scroll = self.cursor + 1 - height;
+        }
+
+        let state = self.render_state();
+        let mut screen = String::from("\x1B[H");
+        for row in 0..height {
+            screen += &self.render_source_line(self.scroll + row, source_width);
+            screen += "│";
+            screen += state.get(row).map(String::as_str).unwrap_or("");
+            screen += "\x1B[K\r\n";
+        }
+        screen += &format!("{}\x1B[K\r\n", fit(&self.message, cols));
+        let help = match self.mode {
+            Mode::Normal => HELP,
+            Mode::Edit { .. } => EDIT_HELP,
+        };
+        screen += &format!("{}{}{}\x1B[K", REVERSE, fit(help, cols), RESET);
+
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(screen.as_bytes())?;
+        stdout.flush()
+    }
+
+    fn render_source_line(&self, line: usize, width: usize) -> String {
+        let Some(text) = self.source.get(line) else {
+            return " ".repeat(width);
+        };
+        let cursor = if line == self.cursor { '>' } else { ' ' };
+        let breakpoint = if self.has_breakpoint(line) {
+            format!("{}●{}", BREAKPOINT, RESET)
+        } else {
+            String::from(" ")
+        };
+        let content = fit(&format!("{:>3} {}", line + 1, text), width - 2);
+
+        if self.current_line() == Some(line) {
+            format!("{}{}{}{}{}", cursor, breakpoint, REVERSE, content, RESET)
+        } else {
+            format!("{}{}{}", cursor, breakpoint, content)
+        }
+    }
+
+    fn render_state(&self) -> Vec<String> {
+        let bits = self.runtime.get_status_bits();
+        let status = if self.halted { "halted" } else { "running" };
+        let mut lines = vec![
+            format!(" Accumulator  {}", self.runtime.get_accumulator()),
+            format!(
+                " Flags        C={} N={} Z={}",
+                bits.carry as u8, bits.negative as u8, bits.zero as u8
+            ),
+            format!(" Step         {}", self.runtime.get_step()),
+            format!(" Status       {}", status),
+            String::new(),
+            String::from(" Registers"),
+            (0..GRID_COLUMNS).fold(String::from("     "), |line, column| {
+                line + &format!(" {:>3}", format!("+{}", column))
+            }),
+        ];
+
+        let register = self.runtime.get_register();
+        for (row, chunk) in register.chunks(GRID_COLUMNS).enumerate() {
+            let mut line = format!(" {:>3} ", row * GRID_COLUMNS);
+            for (column, value) in chunk.iter().enumerate() {
+                let index = row * GRID_COLUMNS + column;
+                let cell = format!("{:>3} ", value.to_string());
+                if self.mode == (Mode::Edit { register: index }) {
+                    line += &format!("{}{}{}", REVERSE, cell, RESET);
+                } else if self.previous_register.get(index) != Some(value) {
+                    line += &format!("{}{}{}", CHANGED, cell, RESET);
+                } else {
+                    line += &cell;
+                }
+            }
+            lines.push(line);
+        }
+        lines
+    }
+}
+
+fn create_runtime(arguments: &Arguments, program: &Program) -> Runtime {
+    let mut runtime = Runtime::from_program(program.clone());
+    for (index, value) in &arguments.initial_registers {
+        runtime.set_register_value(*index, *value);
+    }
+    runtime
+}
+
+// Cuts or pads the text to exactly `width` characters
+fn fit(text: &str, width: usize) -> String {
+    let mut result: String = text.chars().take(width).collect();
+    let length = result.chars().count();
+    result.push_str(&" ".repeat(width - length));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Debugger, Mode};
+    use crate::args::Arguments;
+    use crate::terminal::Key;
+    use asim::{compile_with, CompileOptions, Nibble};
+
+    fn debugger(arguments: &Arguments) -> Debugger<'_> {
+        let options = CompileOptions {
+            register_count: arguments.registers,
+            ..Default::default()
+        };
+        let program = compile_with("NOP", &options).unwrap();
+        Debugger::new(arguments, "NOP", program)
+    }
+
+    fn arguments(registers: usize) -> Arguments {
+        let args = ["debug", "a.asm", "--registers", &registers.to_string()];
+        Arguments::parse(args.into_iter().map(String::from)).unwrap()
+    }
+
+    #[test]
+    fn test_edit_digits() {
+        let arguments = arguments(16);
+        let mut debugger = debugger(&arguments);
+        debugger.handle_key(Key::Char('e'));
+        assert_eq!(debugger.mode, Mode::Edit { register: 0 });
+
+        // `e` and `q` are different keys in edit mode
+        debugger.handle_key(Key::Char('e'));
+        assert_eq!(debugger.mode, Mode::Edit { register: 0 });
+        assert_eq!(debugger.runtime.get_register_value(0), Nibble::from(14u8));
+        debugger.handle_key(Key::Right);
+        debugger.handle_key(Key::Char('f'));
+        assert_eq!(debugger.runtime.get_register_value(1), Nibble::from(15u8));
+        debugger.handle_key(Key::Char('+'));
+        assert_eq!(debugger.runtime.get_register_value(1), Nibble::from(0u8));
+        debugger.handle_key(Key::Char('-'));
+        assert_eq!(debugger.runtime.get_register_value(1), Nibble::from(15u8));
+
+        for key in [Key::Escape, Key::Char('q'), Key::Enter] {
+            debugger.handle_key(Key::Char('e'));
+            assert!(debugger.handle_key(key));
+            assert_eq!(debugger.mode, Mode::Normal);
+        }
+    }
+
+    #[test]
+    fn test_edit_selection() {
+        let arguments = arguments(6);
+        let mut debugger = debugger(&arguments);
+        debugger.handle_key(Key::Char('e'));
+
+        debugger.handle_key(Key::Left);
+        assert_eq!(debugger.mode, Mode::Edit { register: 0 });
+        debugger.handle_key(Key::Down);
+        assert_eq!(debugger.mode, Mode::Edit { register: 4 });
+        // Selections behind the last register are ignored
+        debugger.handle_key(Key::Down);
+        debugger.handle_key(Key::Right);
+        debugger.handle_key(Key::Right);
+        assert_eq!(debugger.mode, Mode::Edit { register: 5 });
+        debugger.handle_key(Key::Up);
+        assert_eq!(debugger.mode, Mode::Edit { register: 1 });
+    }
+
+    #[test]
+    fn test_edit_without_registers() {
+        let arguments = arguments(0);
+        let mut debugger = debugger(&arguments);
+        debugger.handle_key(Key::Char('e'));
+        assert_eq!(debugger.mode, Mode::Normal);
+        assert_eq!(debugger.message, "There are no registers to edit");
+
+        for key in [Key::Char('+'), Key::Char('3')] {
+            debugger.handle_key(key);
+            assert_eq!(debugger.mode, Mode::Normal);
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use std::io::IsTerminal;
+use std::process::ExitCode;
+
+use args::{ArgumentError, Arguments, Subcommand, TraceFormat, USAGE};
+use asim::{
+    compile_with_loader, format_source, CompileOptions, FileLoader, Program, Runtime,
+    RuntimeError, StopReason, Trace,
+};
+
+mod args;
+mod debugger;
+mod terminal;
+
+const EXIT_COMPILE_ERROR: u8 = 1;
+const EXIT_USAGE_ERROR: u8 = 2;
+const EXIT_RUNTIME_ERROR: u8 = 3;
+const EXIT_STEP_LIMIT: u8 = 4;
+const EXIT_NOT_FORMATTED: u8 = 1;
+
+fn main() -> ExitCode {
+    let arguments = match Arguments::parse(std::env::args().skip(1)) {
+        Ok(arguments) => arguments,
+        Err(ArgumentError::Help) => {
+            print!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(ArgumentError::Invalid(message)) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            return ExitCode::from(EXIT_USAGE_ERROR);
+        }
+    };
+
+    let file = arguments.file.display().to_string();
+    let text = match std::fs::read_to_string(&arguments.file) {
+        Ok(text) => text,
+        Err(err) => {
+            eprintln!("error: unable to read `{}`: {}", file, err);
+            return ExitCode::from(EXIT_USAGE_ERROR);
+        }
+    };
+
+    // Files with errors can be formatted as well
+    if arguments.subcommand == Subcommand::Fmt {
+        return format(&arguments, &file, &text);
+    }
+
+    let options = CompileOptions {
+        register_count: arguments.registers,
+        ..Default::default()
+    };
+    // Colours only for a terminal, `NO_COLOR` disables them
+    let color = std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    let program = match compile_with_loader(&file, &FileLoader, &options) {
+        Ok(program) => program,
+        Err(report) => {
+            eprint!("{}", report.render(color));
+            return ExitCode::from(EXIT_COMPILE_ERROR);
+        }
+    };
+    eprint!("{}", program.diagnostics.render(color));
+
+    match arguments.subcommand {
+        Subcommand::Check => {
+            println!("{}: ok, {} commands", file, program.commands.len());
+            ExitCode::SUCCESS
+        }
+        Subcommand::Fmt => unreachable!("formatted before compiling"),
+        Subcommand::Run => run(&arguments, program),
+        Subcommand::Trace => trace(&arguments, program),
+        Subcommand::Debug => match debugger::debug(&arguments, &text, program) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("error: unable to run the debugger: {}", err);
+                ExitCode::from(EXIT_RUNTIME_ERROR)
+            }
+        },
+    }
+}
+
+fn format(arguments: &Arguments, file: &str, text: &str) -> ExitCode {
+    let formatted = format_source(text);
+    if arguments.check {
+        if formatted == text {
+            return ExitCode::SUCCESS;
+        }
+        eprintln!("{}: not formatted", file);
+        return ExitCode::from(EXIT_NOT_FORMATTED);
+    }
+    if !arguments.write {
+        print!("{}", formatted);
+        return ExitCode::SUCCESS;
+    }
+    if formatted != text {
+        if let Err(err) = std::fs::write(&arguments.file, formatted) {
+            eprintln!("error: unable to write `{}`: {}", file, err);
+            return ExitCode::from(EXIT_USAGE_ERROR);
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+fn create_runtime(arguments: &Arguments, program: Program) -> Runtime {
+    let mut runtime = Runtime::from_program(program);
+    // `run` and `trace` execute the whole program, `BRK` markers are ignored
+    runtime.clear_breakpoints();
+    for (index, value) in &arguments.initial_registers {
+        runtime.set_register_value(*index, *value);
+    }
+    runtime
+}
+
+fn run(arguments: &Arguments, program: Program) -> ExitCode {
+    let files = program.files.clone();
+    let mut runtime = create_runtime(arguments, program);
+    let result = runtime.run_until_break(arguments.max_steps);
+
+    print_state(&runtime);
+    exit_code(result, &files)
+}
+
+fn trace(arguments: &Arguments, program: Program) -> ExitCode {
+    let files = program.files.clone();
+    let mut runtime = create_runtime(arguments, program);
+    runtime.start_trace();
+    let result = runtime.run_until_break(arguments.max_steps);
+    let trace = runtime.stop_trace().unwrap_or_default();
+
+    match arguments.format {
+        TraceFormat::Table => print_trace_table(&trace, &files),
+        TraceFormat::Csv => print!("{}", trace.to_csv()),
+        TraceFormat::Json => print!("{}", trace.to_json_lines()),
+    }
+    exit_code(result, &files)
+}
+
+fn exit_code(result: Result<StopReason, RuntimeError>, files: &[String]) -> ExitCode {
+    match result {
+        Ok(StopReason::StepLimit) => {
+            eprintln!("error: the program did not halt within the step limit");
+            ExitCode::from(EXIT_STEP_LIMIT)
+        }
+        Ok(_) => ExitCode::SUCCESS,
+        Err(err) => {
+            match err.line() {
+                Some(line) => eprintln!(
+                    "error in {}: {}",
+                    source_line(files, err.file(), line),
+                    err
+                ),
+                None => eprintln!("error: {}", err),
+            }
+            ExitCode::from(EXIT_RUNTIME_ERROR)
+        }
+    }
+}
+
+fn print_state(runtime: &Runtime) {
+    let bits = runtime.get_status_bits();
+    println!("Steps:       {}", runtime.get_step());
+    println!("Accumulator: {}", runtime.get_accumulator());
+    println!(
+        "Flags:       C={} N={} Z={}",
+        bits.carry as u8, bits.negative as u8, bits.zero as u8
+    );
+    println!("Registers:");
+    for (index, chunk) in runtime.get_register().chunks(8).enumerate() {
+        let cells: Vec<String> = chunk
+            .iter()
+            .enumerate()
+            .map(|(offset, value)| format!("{:>2}={:<2}", index * 8 + offset, value.to_string()))
+            .collect();
+        println!("    {}", cells.join(" "));
+    }
+}
+
+// `line 3` in the first file, `lib.asm:3` in an included file
+fn source_line(files: &[String], file: usize, line: usize) -> String {
+    match file {
+        0 => format!("line {}", line + 1),
+        _ => format!("{}:{}", files[file], line + 1),
+    }
+}
+
+fn print_trace_table(trace: &Trace, files: &[String]) {
+    // Lines of included files are written as `lib.asm:3`, the column grows with them
+    let sources: Vec<String> = trace
+        .get_rows()
+        .iter()
+        .map(|row| match row.file {
+            0 => (row.line + 1).to_string(),
+            file => format!("{}:{}", files[file], row.line + 1),
+        })
+        .collect();
+    let width = sources.iter().map(String::len).max().unwrap_or(0).max(4);
+
+    println!(
+        "{:>5} {:>3} {:<10} {:>width$} {:>3} C N Z  Register",
+        "Step", "IC", "Command", "Line", "Acc"
+    );
+    for (row, source) in trace.get_rows().iter().zip(sources) {
+        let register = match row.changed_register {
+            Some((index, value)) => format!("({}) = {}", index, value),
+            None => String::new(),
+        };
+        let line = format!(
+            "{:>5} {:>3} {:<10} {:>width$} {:>3} {} {} {}  {}",
+            row.step,
+            row.instruction_counter,
+            row.command.to_string(),
+            source,
+            row.accumulator.to_string(),
+            row.status.carry as u8,
+            row.status.negative as u8,
+            row.status.zero as u8,
+            register
+        );
+        println!("{}", line.trim_end());
+    }
+}
@@ -0,0 +1,114 @@
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+
+/// Key pressed by the user
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Key {
+    Char(char),
+    Up,
+    Down,
+    Left,
+    Right,
+    Enter,
+    Escape,
+    Interrupt,
+    Other,
+}
+
+/// Switches the terminal into raw mode and the alternate screen
+///
+/// The previous settings are restored when the value is dropped.
+pub struct Terminal {
+    saved: String,
+}
+
+impl Terminal {
+    pub fn enter() -> io::Result<Self> {
+        let saved = stty(&["-g"])?.trim().to_string();
+        stty(&["raw", "-echo"])?;
+        let mut stdout = io::stdout();
+        // Enter the alternate screen and hide the cursor
+        write!(stdout, "\x1B[?1049h\x1B[?25l")?;
+        stdout.flush()?;
+        Ok(Self { saved })
+    }
+
+    /// Number of rows and columns, falls back to 24x80
+    pub fn size(&self) -> (usize, usize) {
+        stty(&["size"])
+            .ok()
+            .and_then(|size| {
+                let (rows, cols) = size.trim().split_once(' ')?;
+                Some((rows.parse().ok()?, cols.parse().ok()?))
+            })
+            .filter(|&(rows, cols)| rows > 0 && cols > 0)
+            .unwrap_or((24, 80))
+    }
+
+    pub fn read_key(&self) -> io::Result<Key> {
+        // A key is sent at once, so a lone escape byte is the escape key itself
+        let mut buffer = [0; 8];
+        let count = io::stdin().lock().read(&mut buffer)?;
+        if count == 0 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+        Ok(parse_key(&buffer[..count]))
+    }
+}
+
+fn parse_key(bytes: &[u8]) -> Key {
+    match bytes {
+        [b'\r' | b'\n', ..] => Key::Enter,
+        [0x03, ..] => Key::Interrupt,
+        [0x1B] => Key::Escape,
+        // Arrow keys are sent as `ESC [ A` to `ESC [ D`
+        [0x1B, b'[', b'A', ..] => Key::Up,
+        [0x1B, b'[', b'B', ..] => Key::Down,
+        [0x1B, b'[', b'C', ..] => Key::Right,
+        [0x1B, b'[', b'D', ..] => Key::Left,
+        [byte, ..] if byte.is_ascii_graphic() || *byte == b' ' => Key::Char(*byte as char),
+        _ => Key::Other,
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        // Show the cursor and leave the alternate screen
+        let _ = write!(stdout, "\x1B[?25h\x1B[?1049l");
+        let _ = stdout.flush();
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+// `stty` acts on the terminal connected to its standard input
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::piped())
+        .output()?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr);
+        return Err(io::Error::other(message.trim().to_string()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_key, Key};
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(parse_key(b"\x1B"), Key::Escape);
+        assert_eq!(parse_key(b"\x1B[A"), Key::Up);
+        assert_eq!(parse_key(b"\x1B[D"), Key::Left);
+        assert_eq!(parse_key(b"\x1BO"), Key::Other);
+        assert_eq!(parse_key(b"\r"), Key::Enter);
+        assert_eq!(parse_key(b"\x03"), Key::Interrupt);
+        assert_eq!(parse_key(b"e"), Key::Char('e'));
+        assert_eq!(parse_key(b" "), Key::Char(' '));
+        assert_eq!(parse_key(b"\x7F"), Key::Other);
+    }
+}
@@ -1,12 +1,21 @@
+use crate::error::InvalidOpcode;
+use crate::nibble::Nibble;
+
 /// Executable command for the runtime.
 ///
 /// The `Command` struct encapsulates an instruction to be executed, along with its operand
-/// and the line number from which it was generated.
+/// and the file and line number from which it was generated.
+/// Commands marked with `BRK` in the source code have `breakpoint` set, the
+/// [`Runtime`](crate::Runtime) stops in front of them.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Command {
     pub instruction: Instruction,
     pub operant: usize,
+    /// Line inside of the file, commands of a macro have the line of the call
     pub line: usize,
+    /// Index into [`Program::files`](crate::Program::files), `0` for the first file
+    pub file: usize,
+    pub breakpoint: bool,
 }
 
 impl Command {
@@ -15,14 +24,35 @@ impl Command {
             instruction,
             operant,
             line,
+            file: 0,
+            breakpoint: false,
         }
     }
 }
 
+impl Command {
+    /// Operand of a branch as signed 4-bit two's complement
+    ///
+    /// # Example
+    /// ```
+    /// # use asim::{compile, Command};
+    /// let commands = compile("BRZ #-2\nBRN #3").unwrap().commands;
+    ///
+    /// assert_eq!(commands[0].operant, 14);
+    /// assert_eq!(commands[0].get_branch_offset(), -2);
+    /// assert_eq!(commands[1].get_branch_offset(), 3);
+    /// ```
+    pub fn get_branch_offset(&self) -> isize {
+        Nibble::from(self.operant).as_signed() as isize
+    }
+}
+
 impl std::fmt::Display for Command {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self.instruction {
-            Instruction::NOP => write!(f, "NOP"),
+            // The operand has no effect, it is only written if it was set
+            Instruction::NOP if self.operant == 0 => write!(f, "NOP"),
+            Instruction::NOP => write!(f, "NOP #{}", self.operant),
             Instruction::LoadFix => {
                 write!(f, "LDA #{}", self.operant)
             }
@@ -48,13 +78,13 @@ impl std::fmt::Display for Command {
                 write!(f, "JMP {}", self.operant)
             }
             Instruction::BRZ => {
-                write!(f, "BRZ #{}", self.operant)
+                write!(f, "BRZ #{}", self.get_branch_offset())
             }
             Instruction::BRC => {
-                write!(f, "BRC #{}", self.operant)
+                write!(f, "BRC #{}", self.get_branch_offset())
             }
             Instruction::BRN => {
-                write!(f, "BRN #{}", self.operant)
+                write!(f, "BRN #{}", self.get_branch_offset())
             }
         }
     }
@@ -78,19 +108,72 @@ pub enum Instruction {
     BRN = 11,
 }
 
+impl Instruction {
+    /// Opcode of the instruction as it is stored in the memory of the processor.
+    ///
+    /// # Example
+    /// ```
+    /// use asim::Instruction;
+    ///
+    /// assert_eq!(Instruction::JMP.opcode(), 8);
+    /// assert_eq!(Instruction::try_from(8), Ok(Instruction::JMP));
+    /// ```
+    pub fn opcode(&self) -> u8 {
+        *self as u8
+    }
+}
+
+impl TryFrom<u8> for Instruction {
+    type Error = InvalidOpcode;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => Instruction::NOP,
+            1 => Instruction::LoadFix,
+            2 => Instruction::LoadFromRegister,
+            3 => Instruction::SaveToRegister,
+            4 => Instruction::AddFix,
+            5 => Instruction::AddFromRegister,
+            6 => Instruction::SubFix,
+            7 => Instruction::SubFromRegister,
+            8 => Instruction::JMP,
+            9 => Instruction::BRZ,
+            10 => Instruction::BRC,
+            11 => Instruction::BRN,
+            _ => return Err(InvalidOpcode(value)),
+        })
+    }
+}
+
+// Instructions as written in the source code and their allowed operands
+const INSTRUCTIONS: [(&str, u8); 9] = [
+    ("NOP", 0b00000000),
+    ("LDA", 0b00000011),
+    ("STA", 0b00000010),
+    ("ADD", 0b00000011),
+    ("SUB", 0b00000011),
+    ("JMP", 0b00000101),
+    ("BRZ", 0b00000101),
+    ("BRC", 0b00000101),
+    ("BRN", 0b00000101),
+];
+
+/// Names of all instructions as written in the source code
+pub const MNEMONICS: [&str; 9] = {
+    let mut mnemonics = [""; 9];
+    let mut index = 0;
+    while index < INSTRUCTIONS.len() {
+        mnemonics[index] = INSTRUCTIONS[index].0;
+        index += 1;
+    }
+    mnemonics
+};
+
 pub fn get_instruction_attribute(str: &str) -> Option<InstructionAttribute> {
-    Some(match str.to_uppercase().as_str() {
-        "NOP" => InstructionAttribute::from(0b00000000),
-        "LDA" => InstructionAttribute::from(0b00000011),
-        "STA" => InstructionAttribute::from(0b00000010),
-        "ADD" => InstructionAttribute::from(0b00000011),
-        "SUB" => InstructionAttribute::from(0b00000011),
-        "JMP" => InstructionAttribute::from(0b00000101),
-        "BRZ" => InstructionAttribute::from(0b00000001),
-        "BRC" => InstructionAttribute::from(0b00000001),
-        "BRN" => InstructionAttribute::from(0b00000001),
-        _ => return None,
-    })
+    INSTRUCTIONS
+        .iter()
+        .find(|(mnemonic, _)| mnemonic.eq_ignore_ascii_case(str))
+        .map(|&(_, attribute)| InstructionAttribute::from(attribute))
 }
 
 /// ```plaintext
@@ -115,7 +198,7 @@ pub fn get_instruction_attribute(str: &str) -> Option<InstructionAttribute> {
 /// - Bit 7 (8th bit): Don't care
 ///
 #[derive(Debug, Clone, Copy)]
-pub(crate) struct InstructionAttribute(u8);
+pub struct InstructionAttribute(u8);
 
 impl From<u8> for InstructionAttribute {
     fn from(value: u8) -> Self {
@@ -0,0 +1,316 @@
+use crate::error::{Line, ParseError, ParseErrorReport, Severity};
+use std::fmt::{Display, Write};
+
+const RESET: &str = "\x1B[0m";
+const ERROR: &str = "\x1B[1;31m";
+const WARNING: &str = "\x1B[1;33m";
+const NOTE: &str = "\x1B[1;34m";
+const BOLD: &str = "\x1B[1m";
+
+// Tabs are expanded so the markers line up with the source
+const TAB: &str = "    ";
+
+impl ParseErrorReport {
+    /// Formats the warnings and errors like rustc with the affected lines and markers below the spans
+    ///
+    /// Warnings and errors are printed in the order of their position in the source.
+    /// With `color` the output contains ANSI escape codes for terminals. A report
+    /// without warnings and errors results in an empty string.
+    ///
+    /// # Example
+    /// ```
+    /// # use asim::compile;
+    /// let report = compile("LDA #3\nADD #20").unwrap_err();
+    ///
+    /// assert_eq!(
+    ///     report.render(false),
+    ///     "error: Immediate value does not fit into 4 bits
+    ///  --> 2:6
+    ///   |
+    /// 2 | ADD #20
+    ///   |      ^^
+    ///
+    /// error: could not compile due to 1 previous error
+    /// "
+    /// );
+    /// ```
+    pub fn render(&self, color: bool) -> String {
+        let style = Style { color };
+        let mut output = String::new();
+        let mut messages: Vec<&ParseError> = self.warnings.iter().chain(&self.errors).collect();
+        messages.sort_by_key(|error| (error.line.file, error.line.number, error.start));
+        for error in messages {
+            self.render_error(&mut output, error, style);
+        }
+
+        let file = match self.file_name(0) {
+            "" => String::new(),
+            name => format!(" `{}`", name),
+        };
+        let warnings = match self.warnings.len() {
+            0 => String::new(),
+            count => format!("{} warning{}", count, plural(count)),
+        };
+        if !self.errors.is_empty() {
+            let _ = writeln!(
+                output,
+                "{}: could not compile{} due to {} previous error{}{}",
+                style.paint(ERROR, "error"),
+                file,
+                self.errors.len(),
+                plural(self.errors.len()),
+                match warnings.is_empty() {
+                    true => String::new(),
+                    false => format!("; {} emitted", warnings),
+                }
+            );
+        } else if !warnings.is_empty() {
+            let _ = writeln!(
+                output,
+                "{}:{} generated {}",
+                style.paint(WARNING, "warning"),
+                file,
+                warnings
+            );
+        }
+        output
+    }
+
+    fn render_error(&self, output: &mut String, error: &ParseError, style: Style) {
+        let number = (error.line.number + 1).to_string();
+        let gutter = " ".repeat(number.len());
+        let (line, offset, width) = expand(&error.line, error.start, error.end);
+        let (color, title) = match error.severity {
+            Severity::Error => (ERROR, "error"),
+            Severity::Warning => (WARNING, "warning"),
+        };
+
+        let _ = writeln!(
+            output,
+            "{}{}",
+            style.paint(color, title),
+            style.paint(BOLD, &format!(": {}", error.kind))
+        );
+        let _ = writeln!(
+            output,
+            "{}{} {}",
+            gutter,
+            style.paint(NOTE, "-->"),
+            self.location(&error.line, offset)
+        );
+        let _ = writeln!(output, "{} {}", gutter, style.paint(NOTE, "|"));
+        let _ = writeln!(output, "{} {}", style.paint(NOTE, &format!("{} |", number)), line);
+        let _ = writeln!(
+            output,
+            "{} {} {}{}",
+            gutter,
+            style.paint(NOTE, "|"),
+            " ".repeat(offset),
+            style.paint(color, &"^".repeat(width))
+        );
+        if let Some(suggestion) = &error.suggestion {
+            let _ = writeln!(
+                output,
+                "{} {} did you mean `{}`?",
+                gutter,
+                style.paint(BOLD, "= help:"),
+                suggestion
+            );
+        }
+
+        if let Some(invocation) = &error.invocation {
+            let (line, offset, width) = expand(&invocation.line, invocation.start, invocation.end);
+            let _ = writeln!(output, "{} {}", gutter, style.paint(NOTE, "|"));
+            let _ = writeln!(
+                output,
+                "{} {} in the expansion of macro `{}` at {}",
+                gutter,
+                style.paint(BOLD, "= note:"),
+                invocation.name,
+                self.location(&invocation.line, offset)
+            );
+            let _ = writeln!(output, "{} {} {}", gutter, style.paint(NOTE, "|"), line);
+            let _ = writeln!(
+                output,
+                "{} {} {}{}",
+                gutter,
+                style.paint(NOTE, "|"),
+                " ".repeat(offset),
+                style.paint(NOTE, &"-".repeat(width))
+            );
+        }
+        output.push('\n');
+    }
+
+    // `file:line:column`, the file is left out for a text without name
+    fn location(&self, line: &Line, offset: usize) -> String {
+        match self.file_name(line.file) {
+            "" => format!("{}:{}", line.number + 1, offset + 1),
+            name => format!("{}:{}:{}", name, line.number + 1, offset + 1),
+        }
+    }
+
+    fn file_name(&self, file: usize) -> &str {
+        self.files.get(file).map(String::as_str).unwrap_or("")
+    }
+}
+
+impl Display for ParseErrorReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.render(false))
+    }
+}
+
+impl std::error::Error for ParseErrorReport {}
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Style {
+    color: bool,
+}
+
+impl Style {
+    fn paint(&self, code: &str, text: &str) -> String {
+        match self.color {
+            true => format!("{}{}{}", code, text, RESET),
+            false => text.to_string(),
+        }
+    }
+}
+
+// Line with expanded tabs and the column and width of the span in characters
+fn expand(line: &Line, start: usize, end: usize) -> (String, usize, usize) {
+    let width = |text: &str| {
+        text.chars()
+            .map(|c| if c == '\t' { TAB.len() } else { 1 })
+            .sum::<usize>()
+    };
+    let start = start.min(line.value.len());
+    let end = end.clamp(start, line.value.len());
+    (
+        line.value.replace('\t', TAB),
+        width(&line.value[..start]),
+        width(&line.value[start..end]).max(1),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compile, compile_with_loader, CompileOptions};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_render_macro_in_other_file() {
+        let mut files = HashMap::new();
+        files.insert(
+            "main.asm".to_string(),
+            ".include \"lib.asm\"\n\tload 3".to_string(),
+        );
+        files.insert(
+            "lib.asm".to_string(),
+            ".macro load value\n\tLDA #value\n\tSTA (16)\n.endm".to_string(),
+        );
+
+        let report = compile_with_loader("main.asm", &files, &CompileOptions::default())
+            .unwrap_err();
+        assert_eq!(
+            report.to_string(),
+            "error: Address out of range
+ --> lib.asm:3:10
+  |
+3 |     STA (16)
+  |          ^^
+  |
+  = note: in the expansion of macro `load` at main.asm:2:5
+  |     load 3
+  |     ----
+
+error: could not compile `main.asm` due to 1 previous error
+"
+        );
+    }
+
+    #[test]
+    fn test_render_suggestion() {
+        let report = compile("start: NOP\n    LAD #1\n    JMP strat").unwrap_err();
+        assert_eq!(
+            report.render(false),
+            "error: Invalid instruction
+ --> 2:5
+  |
+2 |     LAD #1
+  |     ^^^
+  = help: did you mean `LDA`?
+
+error: Missing label
+ --> 3:9
+  |
+3 |     JMP strat
+  |         ^^^^^
+  = help: did you mean `start`?
+
+error: could not compile due to 2 previous errors
+"
+        );
+    }
+
+    #[test]
+    fn test_render_warnings() {
+        let program = compile("x: NOP 0").unwrap();
+        let output = program.diagnostics.render(false);
+        assert!(output.starts_with("warning: Label is never used\n"));
+        assert!(output.ends_with("\nwarning: generated 2 warnings\n"));
+        assert_eq!(compile("NOP").unwrap().diagnostics.render(false), "");
+
+        let report = compile("NOP 0\nSTA (16)").unwrap_err();
+        assert!(report
+            .render(false)
+            .ends_with("due to 1 previous error; 1 warning emitted\n"));
+    }
+
+    #[test]
+    fn test_render_source_order() {
+        let report = compile("STA (16)\nNOP 0\nADD #20").unwrap_err();
+        assert_eq!(
+            report.render(false),
+            "error: Address out of range
+ --> 1:6
+  |
+1 | STA (16)
+  |      ^^
+
+warning: Operand is ignored
+ --> 2:5
+  |
+2 | NOP 0
+  |     ^
+
+error: Immediate value does not fit into 4 bits
+ --> 3:6
+  |
+3 | ADD #20
+  |      ^^
+
+error: could not compile due to 2 previous errors; 1 warning emitted
+"
+        );
+    }
+
+    #[test]
+    fn test_render_color() {
+        let report = compile("ADD #20\nSTA").unwrap_err();
+        let output = report.render(true);
+
+        assert!(output.starts_with("\x1B[1;31merror\x1B[0m\x1B[1m: "));
+        assert!(output.contains("\x1B[1;31m^^\x1B[0m"));
+        assert!(output.ends_with("due to 2 previous errors\n"));
+        assert!(!report.render(false).contains('\x1B'));
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::BTreeSet;
+
+use crate::commands::{Command, Instruction};
+use crate::error::{DecodeError, EncodeError};
+use crate::nibble::Nibble;
+
+#[cfg(test)]
+mod test;
+
+/// Translate a list of commands into a memory image of nibbles
+///
+/// Every command occupies two consecutive nibbles, the opcode of the instruction
+/// followed by the operand. The command at index `n` is therefore stored at the
+/// nibbles `2n` and `2n + 1`.
+/// An error is returned if an operand does not fit into a single nibble.
+///
+/// # Example
+/// ```
+/// # use asim::{compile, encode, Nibble};
+/// let commands = compile("LDA #3\nSTA (1)").unwrap().commands;
+///
+/// let image = encode(&commands).unwrap();
+/// assert_eq!(image, vec![Nibble::from(1), Nibble::from(3), Nibble::from(3), Nibble::from(1)]);
+/// ```
+pub fn encode(commands: &[Command]) -> Result<Vec<Nibble>, EncodeError> {
+    let mut image: Vec<Nibble> = Vec::with_capacity(commands.len() * 2);
+
+    for command in commands {
+        let (opcode, operant) = encode_command(command)?;
+        image.push(Nibble::from(opcode));
+        image.push(Nibble::from(operant));
+    }
+    Ok(image)
+}
+
+/// Translate a list of commands into a memory image of bytes
+///
+/// Every command occupies a single byte, the opcode is stored in the high nibble
+/// and the operand in the low nibble.
+///
+/// # Example
+/// ```
+/// # use asim::{compile, encode_bytes};
+/// let commands = compile("LDA #3\nSTA (1)").unwrap().commands;
+///
+/// let image = encode_bytes(&commands).unwrap();
+/// assert_eq!(image, vec![0x13, 0x31]);
+/// ```
+pub fn encode_bytes(commands: &[Command]) -> Result<Vec<u8>, EncodeError> {
+    commands
+        .iter()
+        .map(|command| {
+            let (opcode, operant) = encode_command(command)?;
+            Ok((opcode << 4) | operant)
+        })
+        .collect()
+}
+
+fn encode_command(command: &Command) -> Result<(u8, u8), EncodeError> {
+    if command.operant > Nibble::MAX as usize {
+        return Err(EncodeError::OperantOutOfRange { command: *command });
+    }
+    Ok((command.instruction.opcode(), command.operant as u8))
+}
+
+/// Translate a memory image of nibbles back into a list of commands
+///
+/// This is the reverse of [`encode`]. The line of every command is set to its
+/// index, which matches the line numbers of the source created by [`disassemble`].
+///
+/// # Example
+/// ```
+/// # use asim::{compile, decode, encode};
+/// let commands = compile("LDA #3\nSTA (1)").unwrap().commands;
+///
+/// let image = encode(&commands).unwrap();
+/// assert_eq!(decode(&image).unwrap(), commands);
+/// ```
+pub fn decode(image: &[Nibble]) -> Result<Vec<Command>, DecodeError> {
+    image
+        .chunks(2)
+        .enumerate()
+        .map(|(index, chunk)| match chunk {
+            [opcode, operant] => decode_command(index, index * 2, opcode.get_value(), *operant),
+            _ => Err(DecodeError::IncompleteCommand { address: index * 2 }),
+        })
+        .collect()
+}
+
+/// Translate a memory image of bytes back into a list of commands
+///
+/// This is the reverse of [`encode_bytes`].
+pub fn decode_bytes(image: &[u8]) -> Result<Vec<Command>, DecodeError> {
+    image
+        .iter()
+        .enumerate()
+        .map(|(index, byte)| {
+            decode_command(index, index, byte >> 4, Nibble::from(byte & Nibble::MAX))
+        })
+        .collect()
+}
+
+fn decode_command(
+    index: usize,
+    address: usize,
+    opcode: u8,
+    operant: Nibble,
+) -> Result<Command, DecodeError> {
+    let instruction = Instruction::try_from(opcode)
+        .map_err(|_| DecodeError::InvalidOpcode { address, opcode })?;
+    Ok(Command::new(instruction, operant.into(), index))
+}
+
+/// Translate a list of commands into source code that [`compile`](crate::compile) accepts
+///
+/// Every command is written to its own line. A label named after the index of the
+/// command, e.g. `L3`, is created for each target of a `JMP` inside the program.
+///
+/// # Example
+/// ```
+/// # use asim::{compile, disassemble};
+/// let commands = compile("start: SUB #1\nJMP start").unwrap().commands;
+///
+/// assert_eq!(disassemble(&commands), "L0: SUB #1\n    JMP L0\n");
+/// ```
+pub fn disassemble(commands: &[Command]) -> String {
+    let labels: BTreeSet<usize> = commands
+        .iter()
+        .filter(|command| command.instruction == Instruction::JMP)
+        .map(|command| command.operant)
+        .filter(|target| *target < commands.len())
+        .collect();
+
+    let label_width = labels
+        .iter()
+        .last()
+        .map(|last| label_name(*last).len() + 2)
+        .unwrap_or(0);
+
+    let mut text = String::new();
+    for (index, command) in commands.iter().enumerate() {
+        let label = match labels.contains(&index) {
+            true => format!("{}:", label_name(index)),
+            false => String::new(),
+        };
+        let instruction = match command.instruction {
+            Instruction::JMP if labels.contains(&command.operant) => {
+                format!("JMP {}", label_name(command.operant))
+            }
+            _ => command.to_string(),
+        };
+        let instruction = match command.breakpoint {
+            true => format!("BRK {}", instruction),
+            false => instruction,
+        };
+        text.push_str(&format!(
+            "{:<width$}{}\n",
+            label,
+            instruction,
+            width = label_width
+        ));
+    }
+    text
+}
+
+fn label_name(index: usize) -> String {
+    format!("L{}", index)
+}
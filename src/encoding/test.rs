@@ -0,0 +1,164 @@
+use crate::commands::{Command, Instruction};
+use crate::error::{DecodeError, EncodeError, InvalidOpcode};
+use crate::frontend::compile;
+use crate::nibble::Nibble;
+
+use super::{decode, decode_bytes, disassemble, encode, encode_bytes};
+
+#[test]
+fn test_encode() {
+    let commands = vec![
+        Command::new(Instruction::LoadFix, 3, 0),
+        Command::new(Instruction::BRZ, 4, 1),
+        Command::new(Instruction::SaveToRegister, 15, 2),
+        Command::new(Instruction::SubFix, 1, 3),
+        Command::new(Instruction::JMP, 1, 4),
+        Command::new(Instruction::NOP, 0, 5),
+    ];
+
+    let expected_image: Vec<Nibble> = [1, 3, 9, 4, 3, 15, 6, 1, 8, 1, 0, 0]
+        .into_iter()
+        .map(Nibble::from)
+        .collect();
+
+    assert_eq!(encode(&commands), Ok(expected_image));
+    assert_eq!(
+        encode_bytes(&commands),
+        Ok(vec![0x13, 0x94, 0x3F, 0x61, 0x81, 0x00])
+    );
+}
+
+#[test]
+fn test_encode_empty() {
+    assert_eq!(encode(&[]), Ok(vec![]));
+    assert_eq!(encode_bytes(&[]), Ok(vec![]));
+}
+
+#[test]
+fn test_encode_operant_out_of_range() {
+    let command = Command::new(Instruction::JMP, 16, 3);
+    let commands = vec![Command::new(Instruction::NOP, 0, 2), command];
+
+    assert_eq!(
+        encode(&commands),
+        Err(EncodeError::OperantOutOfRange { command })
+    );
+    assert_eq!(
+        encode_bytes(&commands),
+        Err(EncodeError::OperantOutOfRange { command })
+    );
+}
+
+#[test]
+fn test_opcode_round_trip() {
+    for opcode in 0..=11 {
+        let instruction = Instruction::try_from(opcode).unwrap();
+        assert_eq!(instruction.opcode(), opcode);
+    }
+
+    assert_eq!(Instruction::try_from(12), Err(InvalidOpcode(12)));
+    assert_eq!(Instruction::try_from(0xFF), Err(InvalidOpcode(0xFF)));
+}
+
+#[test]
+fn test_decode() {
+    let image: Vec<Nibble> = [1, 3, 9, 4, 3, 15, 8, 1]
+        .into_iter()
+        .map(Nibble::from)
+        .collect();
+    let expected_commands = vec![
+        Command::new(Instruction::LoadFix, 3, 0),
+        Command::new(Instruction::BRZ, 4, 1),
+        Command::new(Instruction::SaveToRegister, 15, 2),
+        Command::new(Instruction::JMP, 1, 3),
+    ];
+
+    assert_eq!(decode(&image), Ok(expected_commands.clone()));
+    assert_eq!(
+        decode_bytes(&[0x13, 0x94, 0x3F, 0x81]),
+        Ok(expected_commands)
+    );
+}
+
+#[test]
+fn test_decode_invalid_image() {
+    let image: Vec<Nibble> = [1, 3, 12, 0].into_iter().map(Nibble::from).collect();
+    assert_eq!(
+        decode(&image),
+        Err(DecodeError::InvalidOpcode {
+            address: 2,
+            opcode: 12
+        })
+    );
+
+    let image: Vec<Nibble> = [1, 3, 2].into_iter().map(Nibble::from).collect();
+    assert_eq!(
+        decode(&image),
+        Err(DecodeError::IncompleteCommand { address: 2 })
+    );
+
+    assert_eq!(
+        decode_bytes(&[0x13, 0xF0]),
+        Err(DecodeError::InvalidOpcode {
+            address: 1,
+            opcode: 15
+        })
+    );
+}
+
+#[test]
+fn test_disassemble() {
+    let commands = vec![
+        Command::new(Instruction::LoadFix, 3, 0),
+        Command::new(Instruction::BRZ, 4, 1),
+        Command::new(Instruction::SaveToRegister, 1, 2),
+        Command::new(Instruction::SubFix, 1, 3),
+        Command::new(Instruction::JMP, 1, 4),
+        Command::new(Instruction::JMP, 12, 5),
+    ];
+
+    let expected_text = "    LDA #3
+L1: BRZ #4
+    STA (1)
+    SUB #1
+    JMP L1
+    JMP 12
+";
+
+    assert_eq!(disassemble(&commands), expected_text);
+}
+
+#[test]
+fn test_disassemble_round_trip() {
+    let code = "
+    LDA #3
+start:
+    BRZ 4
+    STA (1)
+    SUB #1
+    JMP start
+    ADD (2)
+";
+
+    let image = encode(&compile(code).unwrap().commands).unwrap();
+    let commands = decode(&image).unwrap();
+
+    match compile(&disassemble(&commands)) {
+        Ok(recompiled) => assert_eq!(commands, recompiled.commands),
+        Err(err) => panic!("{:#?}", err),
+    }
+}
+
+#[test]
+fn test_disassemble_nop_operant() {
+    // Every nibble survives decoding, disassembling and compiling again
+    let image: Vec<Nibble> = [0, 5, 0, 0, 0, 15].into_iter().map(Nibble::from).collect();
+    let commands = decode(&image).unwrap();
+    let text = disassemble(&commands);
+    assert_eq!(text, "NOP #5\nNOP\nNOP #15\n");
+
+    let recompiled = compile(&text).unwrap();
+    assert_eq!(recompiled.commands, commands);
+    assert!(recompiled.diagnostics.warnings.is_empty());
+    assert_eq!(encode(&recompiled.commands), Ok(image));
+}
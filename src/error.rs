@@ -1,23 +1,44 @@
-use crate::frontend::LineTable;
+use crate::commands::Command;
+use crate::frontend::{LineTable, SourceMap};
 use std::fmt::Display;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct ParseErrorReport {
     pub errors: Vec<ParseError>,
+    /// Findings that do not stop the compilation
+    pub warnings: Vec<ParseError>,
+    /// Names of the compiled files, the first one includes the others
+    pub files: Vec<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ParseError {
     pub kind: ParseErrorType,
+    pub severity: Severity,
     pub start: usize,
     pub end: usize,
     pub line: Line,
+    /// Macro call that produced the erroneous code, `line` is inside the macro definition
+    pub invocation: Option<MacroInvocation>,
+    /// Similar name that could replace the text between `start` and `end`
+    pub suggestion: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+/// Position of a macro call
+#[derive(Debug, Clone, PartialEq)]
+pub struct MacroInvocation {
+    pub name: String,
+    pub start: usize,
+    pub end: usize,
+    pub line: Line,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Line {
     pub value: String,
     pub number: usize,
+    /// Index into [`ParseErrorReport::files`]
+    pub file: usize,
 }
 
 #[derive(Debug)]
@@ -31,38 +52,67 @@ impl ParseErrorReportBuilder {
     }
 
     pub(crate) fn is_successful(&mut self) -> bool {
-        self.error.len() == 0
+        !self
+            .error
+            .iter()
+            .any(|err| err.kind.severity() == Severity::Error)
     }
 
     pub(crate) fn add(&mut self, err: ParseErrorBuilder) {
         self.error.push(err);
     }
 
-    pub(crate) fn build(self, text: &str, line_table: &LineTable) -> ParseErrorReport {
+    pub(crate) fn build(
+        self,
+        text: &str,
+        line_table: &LineTable,
+        sources: &SourceMap,
+    ) -> ParseErrorReport {
+        let (errors, mut warnings): (Vec<_>, Vec<_>) = self
+            .error
+            .into_iter()
+            .partition(|err| err.kind.severity() == Severity::Error);
+        // Warnings come from several passes and are shown in the order of the source
+        warnings.sort_by_key(|warning| warning.start);
+
+        let build = |errors: Vec<ParseErrorBuilder>| {
+            errors
+                .into_iter()
+                .map(|err| err.build(text, line_table, sources))
+                .collect()
+        };
         ParseErrorReport {
-            errors: self
-                .error
-                .iter()
-                .map(|err| err.build(text, line_table))
-                .collect(),
+            errors: build(errors),
+            warnings: build(warnings),
+            files: sources.files.clone(),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Whether a finding stops the compilation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ParseErrorType {
     // Missing
     MissingOperant,
     MissingInstruction,
     MissingLabel,
     MissingParenthesisClose,
+    MissingEquals,
+    MissingPath,
 
     // Invalid
     InvalidFixNumber,
     InvalidAddress,
-    // InvalidNumber,
+    InvalidNumber,
     InvalidInstruction,
     InvalidOperant,
+    InvalidDirective,
     // InvalidFixNumberType,
     InvalidToken,
 
@@ -73,6 +123,40 @@ pub enum ParseErrorType {
 
     // label
     LabelReassign,
+    DataReassign,
+
+    // Macro
+    MacroReassign,
+    MacroArgumentCount,
+    MissingMacroEnd,
+
+    // Include
+    IncludeNotFound,
+    IncludeCycle,
+
+    // Range
+    ImmediateOutOfRange,
+    AddressOutOfRange,
+    BranchOutOfRange,
+    BackwardBranchNotAllowed,
+
+    // Warnings
+    UnusedLabel,
+    UnreachableCode,
+    IgnoredOperant,
+    UnusedStore,
+}
+
+impl ParseErrorType {
+    pub fn severity(&self) -> Severity {
+        match self {
+            ParseErrorType::UnusedLabel
+            | ParseErrorType::UnreachableCode
+            | ParseErrorType::IgnoredOperant
+            | ParseErrorType::UnusedStore => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
 }
 
 impl Display for ParseErrorType {
@@ -82,45 +166,293 @@ impl Display for ParseErrorType {
             ParseErrorType::MissingInstruction => write!(f, "Missing instruction"),
             ParseErrorType::MissingLabel => write!(f, "Missing label"),
             ParseErrorType::MissingParenthesisClose => write!(f, "Missing closing parenthesis"),
+            ParseErrorType::MissingEquals => write!(f, "Missing `=`"),
+            ParseErrorType::MissingPath => write!(f, "Missing file path in quotes"),
             ParseErrorType::InvalidFixNumber => write!(f, "Invalid fix number"),
             ParseErrorType::InvalidAddress => write!(f, "Invalid address"),
+            ParseErrorType::InvalidNumber => write!(f, "Invalid digit in number"),
             ParseErrorType::InvalidInstruction => write!(f, "Invalid instruction"),
             ParseErrorType::InvalidOperant => write!(f, "Invalid operand"),
+            ParseErrorType::InvalidDirective => write!(f, "Invalid directive"),
             ParseErrorType::NotAllowedAddress => write!(f, "Not allowed address"),
             ParseErrorType::NotAllowedFixNumber => write!(f, "Not allowed fix number"),
             ParseErrorType::NotAllowedLabel => write!(f, "Not allowed label"),
             ParseErrorType::LabelReassign => write!(f, "Label reassignment not allowed"),
+            ParseErrorType::DataReassign => write!(f, "Register already initialised"),
+            ParseErrorType::MacroReassign => write!(f, "Macro redefinition not allowed"),
+            ParseErrorType::MacroArgumentCount => write!(f, "Wrong number of macro arguments"),
+            ParseErrorType::MissingMacroEnd => write!(f, "Missing `.endm`"),
+            ParseErrorType::IncludeNotFound => write!(f, "File could not be read"),
+            ParseErrorType::IncludeCycle => write!(f, "File includes itself"),
             ParseErrorType::InvalidToken => write!(f, "Invalid Token"),
+            ParseErrorType::ImmediateOutOfRange => {
+                write!(f, "Immediate value does not fit into 4 bits")
+            }
+            ParseErrorType::AddressOutOfRange => write!(f, "Address out of range"),
+            ParseErrorType::BranchOutOfRange => {
+                write!(f, "Branch offset is not between -8 and 7 commands")
+            }
+            ParseErrorType::BackwardBranchNotAllowed => {
+                write!(f, "Backward branches are not allowed")
+            }
+            ParseErrorType::UnusedLabel => write!(f, "Label is never used"),
+            ParseErrorType::UnreachableCode => write!(f, "Command can never be reached"),
+            ParseErrorType::IgnoredOperant => write!(f, "Operand is ignored"),
+            ParseErrorType::UnusedStore => write!(f, "Register is stored but never read"),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub(crate) struct ParseErrorBuilder {
     kind: ParseErrorType,
     start: usize,
     end: usize,
+    invocation: Option<(usize, usize)>,
+    suggestion: Option<String>,
 }
 
 impl ParseErrorBuilder {
-    pub(crate) fn build(self, text: &str, line_table: &LineTable) -> ParseError {
-        let line_number = line_table.get_line_index_of(self.start);
-        let line_bounds = line_table.get_line_of(self.start);
-        let line_text = &text[(line_bounds.0 + 1)..line_bounds.1];
+    pub(crate) fn build(self, text: &str, line_table: &LineTable, sources: &SourceMap) -> ParseError {
+        let (line, start, end) = locate(text, line_table, sources, self.start, self.end);
         ParseError {
             kind: self.kind,
-            start: self.start - line_bounds.0 - 1,
-            end: self.end - line_bounds.0 - 1,
-            line: Line {
-                value: line_text.to_string(),
-                number: line_number,
-            },
+            severity: self.kind.severity(),
+            start,
+            end,
+            line,
+            invocation: self.invocation.map(|(start, end)| {
+                let (line, relative_start, relative_end) =
+                    locate(text, line_table, sources, start, end);
+                MacroInvocation {
+                    name: text[start..end].to_string(),
+                    start: relative_start,
+                    end: relative_end,
+                    line,
+                }
+            }),
+            suggestion: self.suggestion,
         }
     }
 }
 
+// Line of a span and the span relative to the start of that line
+fn locate(
+    text: &str,
+    line_table: &LineTable,
+    sources: &SourceMap,
+    start: usize,
+    end: usize,
+) -> (Line, usize, usize) {
+    let origin = sources.origin(line_table.get_line_index_of(start));
+    let line_bounds = line_table.get_line_of(start);
+    let line_text = text[line_bounds.start..line_bounds.end].trim_end_matches('\r');
+    let line = Line {
+        value: line_text.to_string(),
+        number: origin.line,
+        file: origin.file,
+    };
+    (
+        line,
+        start - line_bounds.start,
+        end.clamp(start, line_bounds.end) - line_bounds.start,
+    )
+}
+
 impl ParseErrorBuilder {
     pub(crate) fn new(kind: ParseErrorType, start: usize, end: usize) -> Self {
-        Self { kind, start, end }
+        Self {
+            kind,
+            start,
+            end,
+            invocation: None,
+            suggestion: None,
+        }
+    }
+
+    /// Adds a similar name that can replace the reported text
+    pub(crate) fn suggest(mut self, suggestion: Option<&str>) -> Self {
+        self.suggestion = suggestion.map(str::to_string);
+        self
+    }
+
+    /// Records the macro call that produced the code, nested calls keep the outermost one
+    pub(crate) fn invoked_at(mut self, invocation: Option<(usize, usize)>) -> Self {
+        self.invocation = self.invocation.or(invocation);
+        self
+    }
+
+    pub(crate) fn kind(&self) -> ParseErrorType {
+        self.kind
+    }
+
+    /// Moves the error and its macro call to a new position in the text
+    pub(crate) fn move_to<F: Fn(usize) -> usize>(&mut self, position: F) {
+        self.start = position(self.start);
+        self.end = position(self.end);
+        self.invocation = self
+            .invocation
+            .map(|(start, end)| (position(start), position(end)));
+    }
+}
+
+/// Value that does not belong to any [`Instruction`](crate::Instruction).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InvalidOpcode(pub u8);
+
+impl Display for InvalidOpcode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid opcode {}", self.0)
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EncodeError {
+    /// The operand does not fit into a single nibble
+    OperantOutOfRange { command: Command },
+    /// The encoded program does not fit into the memory
+    MemoryTooSmall { required: usize, available: usize },
+    /// An initial register value would overwrite the encoded program
+    DataInProgram { address: usize },
+}
+
+impl Display for EncodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncodeError::OperantOutOfRange { command } => write!(
+                f,
+                "Operand of `{}` in line {} does not fit into a nibble",
+                command, command.line
+            ),
+            EncodeError::MemoryTooSmall {
+                required,
+                available,
+            } => write!(
+                f,
+                "The program requires {} nibbles but the memory only has {}",
+                required, available
+            ),
+            EncodeError::DataInProgram { address } => write!(
+                f,
+                "The initial value of address {} would overwrite the program",
+                address
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DecodeError {
+    /// The image ends between the opcode and the operand of a command
+    IncompleteCommand { address: usize },
+    /// The opcode at the given address does not belong to any instruction
+    InvalidOpcode { address: usize, opcode: u8 },
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::IncompleteCommand { address } => {
+                write!(f, "Missing operand for the opcode at address {}", address)
+            }
+            DecodeError::InvalidOpcode { address, opcode } => {
+                write!(f, "Invalid opcode {} at address {}", opcode, address)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RuntimeError {
+    /// The runtime was created without any commands
+    EmptyProgram,
+    /// The command accesses a register that does not exist
+    AddressOutOfRange { command: Command, address: usize },
+    /// The command jumps behind the end of the program
+    PcOutOfProgram { command: Command, target: usize },
+    /// The branch jumps in front of the first command
+    PcBeforeProgram { command: Command, offset: isize },
+    /// The memory at the instruction counter does not contain a valid opcode
+    ///
+    /// `line` is the source line of the command that was loaded into this slot, `None`
+    /// behind the end of the loaded program, and `file` the file of that line. The
+    /// opcode itself was written while the program ran, so there is no command to show.
+    InvalidOpcode {
+        address: usize,
+        opcode: u8,
+        line: Option<usize>,
+        file: usize,
+    },
+}
+
+impl RuntimeError {
+    /// Command that caused the error, if any
+    pub fn command(&self) -> Option<Command> {
+        match self {
+            RuntimeError::AddressOutOfRange { command, .. }
+            | RuntimeError::PcOutOfProgram { command, .. }
+            | RuntimeError::PcBeforeProgram { command, .. } => Some(*command),
+            RuntimeError::EmptyProgram | RuntimeError::InvalidOpcode { .. } => None,
+        }
+    }
+
+    /// Source line of the command that caused the error, if any
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            RuntimeError::InvalidOpcode { line, .. } => *line,
+            _ => self.command().map(|command| command.line),
+        }
+    }
+
+    /// File of [`line`](Self::line), an index into [`Program::files`](crate::Program::files)
+    pub fn file(&self) -> usize {
+        match self {
+            RuntimeError::InvalidOpcode { file, .. } => *file,
+            _ => self.command().map_or(0, |command| command.file),
+        }
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuntimeError::EmptyProgram => write!(f, "The program is empty"),
+            RuntimeError::AddressOutOfRange { command, address } => write!(
+                f,
+                "`{}` in line {} accesses the register {} which does not exist",
+                command, command.line, address
+            ),
+            RuntimeError::PcOutOfProgram { command, target } => write!(
+                f,
+                "`{}` in line {} jumps to {} which is outside of the program",
+                command, command.line, target
+            ),
+            RuntimeError::PcBeforeProgram { command, offset } => write!(
+                f,
+                "`{}` in line {} branches {} commands back, in front of the program",
+                command,
+                command.line,
+                offset.unsigned_abs()
+            ),
+            RuntimeError::InvalidOpcode {
+                address,
+                opcode,
+                line: Some(line),
+                ..
+            } => write!(
+                f,
+                "Invalid opcode {} at address {}, the command of line {} was overwritten",
+                opcode, address, line
+            ),
+            RuntimeError::InvalidOpcode { address, opcode, .. } => {
+                write!(f, "Invalid opcode {} at address {}", opcode, address)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
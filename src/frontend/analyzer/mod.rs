@@ -0,0 +1,142 @@
+use super::expander::ExpandedStatement;
+use super::lexer::token::TokenType;
+use super::parser::command_builder::CommandBuilder;
+use super::parser::statement::Statement;
+use super::ParseContext;
+use crate::commands::{Command, Instruction};
+use crate::error::{ParseErrorBuilder, ParseErrorType};
+use std::collections::HashSet;
+
+/// Reports warnings about code that compiles but most likely does not work as intended
+///
+/// Requires the commands of a successful resolution, `commands[n]` belongs to the
+/// n-th command statement.
+pub(super) fn analyze(
+    statements: &[ExpandedStatement],
+    commands: &[Command],
+    context: &mut ParseContext,
+) {
+    let builders: Vec<(&CommandBuilder, &ExpandedStatement)> = statements
+        .iter()
+        .filter_map(|entry| match &entry.statement {
+            Statement::Command(command) => Some((command, entry)),
+            _ => None,
+        })
+        .collect();
+    if builders.len() != commands.len() {
+        return;
+    }
+
+    warn_unused_labels(&builders, context);
+    warn_unreachable_code(&builders, commands, context);
+    warn_unused_stores(&builders, commands, context);
+}
+
+fn warn_unused_labels(
+    builders: &[(&CommandBuilder, &ExpandedStatement)],
+    context: &mut ParseContext,
+) {
+    // Labels of macro bodies are only used inside of their expansion
+    let used: HashSet<(&str, Option<usize>)> = builders
+        .iter()
+        .filter_map(|(command, entry)| Some((command.operant.as_ref()?, entry.operant_scope)))
+        .filter(|(operant, _)| operant.value.token_type == TokenType::Symbol)
+        .map(|(operant, scope)| (operant.value.resolve(context.text), scope))
+        .collect();
+
+    for (command, entry) in builders {
+        let Some(label) = &command.label else {
+            continue;
+        };
+        if !used.contains(&(label.resolve(context.text), entry.label_scope)) {
+            context.errors.add(
+                ParseErrorBuilder::new(ParseErrorType::UnusedLabel, label.start, label.end)
+                    .invoked_at(entry.invocation_span()),
+            );
+        }
+    }
+}
+
+// Follows every jump and branch from the first command, the first command of
+// each unreachable block is reported
+fn warn_unreachable_code(
+    builders: &[(&CommandBuilder, &ExpandedStatement)],
+    commands: &[Command],
+    context: &mut ParseContext,
+) {
+    let mut reachable = vec![false; commands.len()];
+    let mut pending = vec![0];
+    while let Some(index) = pending.pop() {
+        if index >= commands.len() || reachable[index] {
+            continue;
+        }
+        reachable[index] = true;
+
+        let command = &commands[index];
+        match command.instruction {
+            Instruction::JMP => pending.push(command.operant),
+            Instruction::BRZ | Instruction::BRC | Instruction::BRN => {
+                pending.push(index + 1);
+                if let Some(target) = index.checked_add_signed(command.get_branch_offset()) {
+                    pending.push(target);
+                }
+            }
+            _ => pending.push(index + 1),
+        }
+    }
+
+    for (index, (command, entry)) in builders.iter().enumerate() {
+        if reachable[index] || (index > 0 && !reachable[index - 1]) {
+            continue;
+        }
+        let end = command
+            .operant
+            .as_ref()
+            .map(|operant| operant.value.end)
+            .unwrap_or(command.instruction.end);
+        context.errors.add(
+            ParseErrorBuilder::new(
+                ParseErrorType::UnreachableCode,
+                command.instruction.start,
+                end,
+            )
+            .invoked_at(entry.invocation_span()),
+        );
+    }
+}
+
+fn warn_unused_stores(
+    builders: &[(&CommandBuilder, &ExpandedStatement)],
+    commands: &[Command],
+    context: &mut ParseContext,
+) {
+    let read: HashSet<usize> = commands
+        .iter()
+        .filter(|command| {
+            matches!(
+                command.instruction,
+                Instruction::LoadFromRegister
+                    | Instruction::AddFromRegister
+                    | Instruction::SubFromRegister
+            )
+        })
+        .map(|command| command.operant)
+        .collect();
+
+    for (command, (builder, entry)) in commands.iter().zip(builders) {
+        if command.instruction != Instruction::SaveToRegister || read.contains(&command.operant) {
+            continue;
+        }
+        let Some(operant) = &builder.operant else {
+            continue;
+        };
+        context.errors.add(
+            ParseErrorBuilder::new(
+                ParseErrorType::UnusedStore,
+                operant.value.start,
+                operant.value.end,
+            )
+            .invoked_at(entry.invocation_span()),
+        );
+    }
+}
@@ -0,0 +1,244 @@
+use super::lexer::token::{Token, TokenType};
+use super::parser::statement::{MacroBuilder, MacroCallBuilder, Statement};
+use super::ParseContext;
+use crate::error::{ParseErrorBuilder, ParseErrorType};
+use std::collections::HashMap;
+
+/// Statement after the macro expansion
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ExpandedStatement {
+    pub(crate) statement: Statement,
+    /// Name of the outermost macro call that produced the statement
+    pub(crate) invocation: Option<Token>,
+    /// Expansion the label of a command is local to, `None` outside of macros
+    pub(crate) label_scope: Option<usize>,
+    /// Expansion whose local labels the operand of a command refers to
+    pub(crate) operant_scope: Option<usize>,
+}
+
+impl ExpandedStatement {
+    pub(super) fn global(statement: Statement) -> Self {
+        Self {
+            statement,
+            invocation: None,
+            label_scope: None,
+            operant_scope: None,
+        }
+    }
+
+    pub(crate) fn invocation_span(&self) -> Option<(usize, usize)> {
+        self.invocation.as_ref().map(|token| (token.start, token.end))
+    }
+}
+
+/// Replaces every macro call with the body of the macro
+///
+/// Definitions are removed, all other statements are kept in order. Labels
+/// defined in a macro body are local to each expansion, so a macro with a
+/// loop can be called more than once.
+pub(super) fn expand_macros(
+    statements: &[Statement],
+    context: &mut ParseContext,
+) -> Vec<ExpandedStatement> {
+    let mut expander = Expander::new();
+
+    for statement in statements {
+        match statement {
+            Statement::Macro(definition) => expander.define(definition, context.text),
+            Statement::MacroCall(call) => expander.expand(call, context),
+            statement => expander
+                .expanded
+                .push(ExpandedStatement::global(statement.clone())),
+        }
+    }
+    expander.expanded
+}
+
+pub(super) struct Expander<'d> {
+    definitions: HashMap<&'d str, &'d MacroBuilder>,
+    expanded: Vec<ExpandedStatement>,
+    /// Number of expansions so far, the index of an expansion is the scope of its labels
+    expansions: usize,
+}
+
+impl<'d> Expander<'d> {
+    pub(super) fn new() -> Self {
+        Self {
+            definitions: HashMap::new(),
+            expanded: vec![],
+            expansions: 0,
+        }
+    }
+
+    /// Makes a macro known, redefinitions were already reported by the parser
+    pub(super) fn define(&mut self, definition: &'d MacroBuilder, text: &'d str) {
+        self.definitions
+            .entry(definition.name.resolve(text))
+            .or_insert(definition);
+    }
+
+    /// Appends the body of the called macro to the expanded statements
+    pub(super) fn expand(&mut self, call: &MacroCallBuilder, context: &mut ParseContext) {
+        let scopes = vec![None; call.arguments.len()];
+        self.expand_call(call, None, &scopes, None, context)
+    }
+
+    /// Expands a single call on its own, the scopes of its labels start at 0
+    ///
+    /// Returns the statements and the number of expansions, the scopes of the calls
+    /// behind it have to be moved by that number.
+    pub(super) fn expand_alone(
+        &mut self,
+        call: &MacroCallBuilder,
+        context: &mut ParseContext,
+    ) -> (Vec<ExpandedStatement>, usize) {
+        self.expansions = 0;
+        self.expand(call, context);
+        (
+            std::mem::take(&mut self.expanded),
+            std::mem::take(&mut self.expansions),
+        )
+    }
+
+    // `caller` is the scope of the call itself, `arguments` the scopes of its arguments
+    fn expand_call(
+        &mut self,
+        call: &MacroCallBuilder,
+        caller: Option<usize>,
+        arguments: &[Option<usize>],
+        invocation: Option<&Token>,
+        context: &mut ParseContext,
+    ) {
+        // The parser only creates calls of defined macros
+        let Some(definition) = self.definitions.get(call.name.resolve(context.text)).copied()
+        else {
+            return;
+        };
+        let invocation = invocation.unwrap_or(&call.name);
+        let first = self.expanded.len();
+        let scope = Some(self.expansions);
+        self.expansions += 1;
+
+        let text = context.text;
+        let locals: Vec<&str> = definition
+            .body
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::Command(command) => command.label.as_ref(),
+                Statement::MacroCall(call) => call.label.as_ref(),
+                _ => None,
+            })
+            .map(|label| label.resolve(text))
+            .collect();
+        // Arguments keep the scope of the call, names of local labels get the new one
+        let scope_of = |token: &Token| match parameter_index(definition, token, text) {
+            Some(index) => arguments[index],
+            None if locals.contains(&token.resolve(text)) => scope,
+            None => None,
+        };
+
+        for statement in &definition.body {
+            match substitute(statement, definition, call, text) {
+                Statement::MacroCall(inner) => {
+                    let scopes: Vec<Option<usize>> =
+                        inner_arguments(statement).iter().map(scope_of).collect();
+                    self.expand_call(&inner, scope, &scopes, Some(invocation), context)
+                }
+                expanded => {
+                    let operant_scope = match statement {
+                        Statement::Command(command) => command
+                            .operant
+                            .as_ref()
+                            .and_then(|operant| scope_of(&operant.value)),
+                        _ => None,
+                    };
+                    self.expanded.push(ExpandedStatement {
+                        statement: expanded,
+                        invocation: Some(invocation.clone()),
+                        label_scope: scope,
+                        operant_scope,
+                    })
+                }
+            }
+        }
+
+        // Label and breakpoint of the call belong to the first command of the expansion
+        let command = self.expanded[first..]
+            .iter_mut()
+            .find_map(|entry| match &mut entry.statement {
+                Statement::Command(command) => Some((command, &mut entry.label_scope)),
+                _ => None,
+            });
+        match (command, &call.label) {
+            (Some((command, _)), Some(label)) if command.label.is_some() => {
+                context.errors.add(label_not_allowed(label, invocation))
+            }
+            (Some((command, label_scope)), label) => {
+                if label.is_some() {
+                    command.label = label.clone();
+                    *label_scope = caller;
+                }
+                command.breakpoint |= call.breakpoint;
+            }
+            (None, Some(label)) => context.errors.add(label_not_allowed(label, invocation)),
+            (None, None) => {}
+        }
+    }
+}
+
+fn inner_arguments(statement: &Statement) -> &[Token] {
+    match statement {
+        Statement::MacroCall(call) => &call.arguments,
+        _ => &[],
+    }
+}
+
+fn label_not_allowed(label: &Token, invocation: &Token) -> ParseErrorBuilder {
+    ParseErrorBuilder::new(ParseErrorType::NotAllowedLabel, label.start, label.end)
+        .invoked_at(Some((invocation.start, invocation.end)))
+}
+
+// Replaces the parameters of the macro with the arguments of the call
+fn substitute(
+    statement: &Statement,
+    definition: &MacroBuilder,
+    call: &MacroCallBuilder,
+    text: &str,
+) -> Statement {
+    let replace = |token: &Token| -> Token {
+        parameter_index(definition, token, text)
+            .map_or_else(|| token.clone(), |index| call.arguments[index].clone())
+    };
+
+    let mut statement = statement.clone();
+    match &mut statement {
+        Statement::Command(command) => {
+            if let Some(operant) = &mut command.operant {
+                operant.value = replace(&operant.value);
+            }
+        }
+        Statement::Data(data) => {
+            data.address = replace(&data.address);
+            data.value = replace(&data.value);
+        }
+        Statement::MacroCall(inner) => {
+            for argument in inner.arguments.iter_mut() {
+                *argument = replace(argument);
+            }
+        }
+        Statement::Constant(_) | Statement::Macro(_) | Statement::Include(_) => {}
+    }
+    statement
+}
+
+// Index of the parameter the token names
+fn parameter_index(definition: &MacroBuilder, token: &Token, text: &str) -> Option<usize> {
+    if token.token_type != TokenType::Symbol {
+        return None;
+    }
+    let name = token.resolve(text);
+    definition
+        .parameters
+        .iter()
+        .position(|parameter| parameter.resolve(text) == name)
+}
@@ -0,0 +1,284 @@
+use super::lexer::token::{LosslessToken, Token, TokenType, Trivia, TriviaKind};
+use super::lexer::{rebuild, tokenize_lossless};
+use super::ParseContext;
+use crate::commands::get_instruction_attribute;
+
+/// Smallest indentation of instructions
+const INDENT: usize = 4;
+
+/// Position of the code of a line
+enum Layout {
+    /// Directives, constants and labels on their own line
+    Start,
+    /// Instructions and macro calls
+    Indented,
+    /// Instruction behind a label, the label is written at the start of the line
+    Labeled(String),
+}
+
+struct FormattedLine<'a> {
+    layout: Layout,
+    code: String,
+    comment: Option<&'a str>,
+    /// Lines with invalid tokens are kept as they are
+    verbatim: bool,
+}
+
+/// Formats source code in one style without changing its meaning
+///
+/// Mnemonics and `BRK` are written in upper case and directives in lower case.
+/// Instructions are indented by at least four spaces, behind labels on the same
+/// line as well, so they line up. Operands are separated by a single space and
+/// trailing `//` comments are aligned within each block of lines. Line numbers stay
+/// the same: only the blank lines at the end of the text are removed and the text
+/// always ends with a single new line. `\r\n` is written as `\n`. Lines containing
+/// invalid tokens are only stripped of trailing whitespace.
+///
+/// Formatting a formatted text does not change it.
+///
+/// # Example
+/// ```
+/// # use asim::format_source;
+/// let text = "start:lda   # 1 // load\n  sta ( 1 )   // store\njmp start";
+///
+/// assert_eq!(
+///     format_source(text),
+///     "start: LDA #1  // load\n       STA (1) // store\n       JMP start\n"
+/// );
+/// ```
+pub fn format_source(text: &str) -> String {
+    let mut context = ParseContext::new_empty(text);
+    let tokens = tokenize_lossless(text, &mut context);
+    let macros = macro_names(&tokens, text);
+
+    // Every line ends with a new line or the end, the comment is trivia in front of it
+    let mut lines = vec![];
+    let mut start = 0;
+    for (index, token) in tokens.iter().enumerate() {
+        if matches!(token.token.token_type, TokenType::NewLine | TokenType::End) {
+            lines.push(format_line(
+                &tokens[start..index],
+                &token.leading,
+                text,
+                &macros,
+            ));
+            start = index + 1;
+        }
+    }
+
+    // Labels in front of instructions move all instructions to the same column
+    let column = lines
+        .iter()
+        .filter_map(|line| match &line.layout {
+            Layout::Labeled(label) => Some(label.chars().count() + 1),
+            _ => None,
+        })
+        .fold(INDENT, usize::max);
+
+    let mut output: Vec<String> = lines
+        .iter()
+        .map(|line| match (&line.layout, line.code.is_empty()) {
+            _ if line.verbatim => line.code.clone(),
+            (Layout::Start, _) | (Layout::Indented, true) => line.code.clone(),
+            (Layout::Indented, false) => format!("{}{}", " ".repeat(column), line.code),
+            (Layout::Labeled(label), _) => {
+                format!("{:<width$}{}", label, line.code, width = column)
+            }
+        })
+        .collect();
+    align_comments(&lines, &mut output, column);
+
+    while output.last().is_some_and(|line| line.is_empty()) {
+        output.pop();
+    }
+    match output.is_empty() {
+        true => String::new(),
+        false => output.join("\n") + "\n",
+    }
+}
+
+fn format_line<'a>(
+    tokens: &[LosslessToken],
+    trailing: &[Trivia],
+    text: &'a str,
+    macros: &[&str],
+) -> FormattedLine<'a> {
+    let comment = trailing
+        .iter()
+        .find(|trivia| trivia.kind == TriviaKind::Comment)
+        .map(|trivia| trivia.resolve(text).trim_end());
+
+    if tokens
+        .iter()
+        .any(|token| token.token.token_type == TokenType::Invalid)
+    {
+        let mut code = rebuild(tokens, text);
+        for trivia in trailing {
+            code += trivia.resolve(text);
+        }
+        return FormattedLine {
+            layout: Layout::Start,
+            code: code.trim_end().to_string(),
+            comment: None,
+            verbatim: true,
+        };
+    }
+    let tokens: Vec<&Token> = tokens.iter().map(|token| &token.token).collect();
+    let Some(first) = tokens.first() else {
+        // Comments on their own line stay at the start or move to the instructions
+        let layout = match trailing.first() {
+            Some(trivia) if trivia.kind == TriviaKind::Whitespace => Layout::Indented,
+            _ => Layout::Start,
+        };
+        return FormattedLine {
+            layout,
+            code: String::new(),
+            comment,
+            verbatim: false,
+        };
+    };
+
+    let is_start = first.token_type == TokenType::Directive
+        || tokens
+            .get(1)
+            .is_some_and(|second| second.token_type == TokenType::Directive);
+    if is_start {
+        return FormattedLine {
+            layout: Layout::Start,
+            code: join(&tokens, text, &[]),
+            comment,
+            verbatim: false,
+        };
+    }
+
+    // `label:` or `BRK label:` in front of the instruction
+    let marker = usize::from(is_breakpoint_marker(&tokens, text));
+    let label_end = match (tokens.get(marker), tokens.get(marker + 1)) {
+        (Some(name), Some(colon))
+            if name.token_type == TokenType::Symbol && colon.token_type == TokenType::Colon =>
+        {
+            marker + 2
+        }
+        _ => 0,
+    };
+    let (label, body) = tokens.split_at(label_end);
+
+    // The instruction follows an optional marker
+    let body_marker = is_breakpoint_marker(body, text);
+    let is_mnemonic = body.get(usize::from(body_marker)).is_some_and(|token| {
+        let name = token.resolve(text);
+        token.token_type == TokenType::Symbol
+            && get_instruction_attribute(name).is_some()
+            && !macros.contains(&name)
+    });
+
+    let label = join(label, text, &[marker == 1]);
+    let upper_case = match body_marker {
+        true => vec![true, is_mnemonic],
+        false => vec![is_mnemonic],
+    };
+    let code = join(body, text, &upper_case);
+    let layout = match (label.is_empty(), code.is_empty()) {
+        (true, _) => Layout::Indented,
+        (false, true) => Layout::Start,
+        (false, false) => Layout::Labeled(label.clone()),
+    };
+    FormattedLine {
+        code: match layout {
+            Layout::Start => label,
+            _ => code,
+        },
+        layout,
+        comment,
+        verbatim: false,
+    }
+}
+
+// Aligns trailing comments of lines without empty line between them
+fn align_comments(lines: &[FormattedLine], output: &mut [String], column: usize) {
+    let mut start = 0;
+    while start < lines.len() {
+        let end = (start..lines.len())
+            .find(|&index| {
+                lines[index].code.is_empty()
+                    && lines[index].comment.is_none()
+                    && !lines[index].verbatim
+            })
+            .unwrap_or(lines.len());
+
+        let comment_column = (start..end)
+            .filter(|&index| !lines[index].code.is_empty() && lines[index].comment.is_some())
+            .map(|index| output[index].chars().count() + 1)
+            .max()
+            .unwrap_or(0);
+        for index in start..end {
+            let Some(comment) = lines[index].comment else {
+                continue;
+            };
+            output[index] = match (lines[index].code.is_empty(), &lines[index].layout) {
+                (true, Layout::Indented) => format!("{}{}", " ".repeat(column), comment),
+                (true, _) => comment.to_string(),
+                (false, _) => format!(
+                    "{:<width$}{}",
+                    output[index],
+                    comment,
+                    width = comment_column
+                ),
+            };
+        }
+        start = end + 1;
+    }
+}
+
+// Tokens separated by single spaces, except inside `(n)`, behind `#` and in front of `:`
+//
+// `upper_case[n]` tells whether the n-th token is a mnemonic or marker.
+fn join(tokens: &[&Token], text: &str, upper_case: &[bool]) -> String {
+    let mut code = String::new();
+    let mut previous: Option<&TokenType> = None;
+    for (index, token) in tokens.iter().enumerate() {
+        let separated = !matches!(
+            (previous, &token.token_type),
+            (None, _)
+                | (Some(TokenType::Hash | TokenType::ParenthesisOpen), _)
+                | (_, TokenType::ParenthesisClose | TokenType::Colon)
+        );
+        if separated {
+            code.push(' ');
+        }
+
+        let value = token.resolve(text);
+        match token.token_type {
+            TokenType::Directive => code += &value.to_lowercase(),
+            _ if upper_case.get(index) == Some(&true) => code += &value.to_uppercase(),
+            _ => code += value,
+        }
+        previous = Some(&token.token_type);
+    }
+    code
+}
+
+// `BRK` is only a marker in front of an instruction, not as an operand or label
+fn is_breakpoint_marker(tokens: &[&Token], text: &str) -> bool {
+    let is_label = tokens
+        .get(1)
+        .is_some_and(|next| next.token_type == TokenType::Colon);
+    tokens.first().is_some_and(|token| {
+        token.token_type == TokenType::Symbol
+            && token.resolve(text).eq_ignore_ascii_case("BRK")
+            && !is_label
+    })
+}
+
+// Macros named like a mnemonic in another case are called instead of the instruction
+fn macro_names<'a>(tokens: &[LosslessToken], text: &'a str) -> Vec<&'a str> {
+    tokens
+        .windows(2)
+        .filter(|pair| {
+            pair[0].token.token_type == TokenType::Directive
+                && pair[0].token.resolve(text).eq_ignore_ascii_case(".macro")
+                && pair[1].token.token_type == TokenType::Symbol
+        })
+        .map(|pair| pair[1].token.resolve(text))
+        .collect()
+}
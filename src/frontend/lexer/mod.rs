@@ -1,128 +1,198 @@
 use super::{LineInfo, ParseContext};
-use std::{iter::Peekable, usize};
-use token::{Token, TokenType};
+use std::iter::Peekable;
+use token::{LosslessToken, Token, TokenType, Trivia, TriviaKind};
 
 #[cfg(test)]
 mod test;
 pub mod token;
 
 pub(super) fn tokenize(text: &str, context: &mut ParseContext) -> Vec<Token> {
-    let mut tokens: Vec<Token> = vec![];
+    lex(text, context, false)
+        .into_iter()
+        .map(|token| token.token)
+        .collect()
+}
+
+/// Tokenizes like [`tokenize`] but keeps whitespace and comments as trivia
+///
+/// [`rebuild`] turns the tokens back into the original text.
+pub(super) fn tokenize_lossless(text: &str, context: &mut ParseContext) -> Vec<LosslessToken> {
+    lex(text, context, true)
+}
+
+// Without `keep_trivia` the leading trivia of every token stays empty
+fn lex(text: &str, context: &mut ParseContext, keep_trivia: bool) -> Vec<LosslessToken> {
+    let mut tokens: Vec<LosslessToken> = vec![];
+    let mut trivia: Vec<Trivia> = vec![];
 
     let mut line_start: usize = 0;
-    let mut counter: usize = 0;
-    let mut cursor = text.chars().enumerate().peekable();
+    let mut cursor = text.char_indices().peekable();
 
     while let Some((index, c)) = cursor.next() {
-        counter += 1;
         let token = match c {
             '#' => Token::new_single(TokenType::Hash, index),
             '(' => Token::new_single(TokenType::ParenthesisOpen, index),
             ')' => Token::new_single(TokenType::ParenthesisClose, index),
             ':' => Token::new_single(TokenType::Colon, index),
+            '=' => Token::new_single(TokenType::Equals, index),
             '\n' => {
                 context.line_table.push(LineInfo {
                     start: line_start,
                     end: index,
                 });
-                line_start = index;
+                line_start = index + 1;
                 Token::new_single(TokenType::NewLine, index)
             }
-            '/' => {
-                let token = check_for_comment(&mut cursor, index);
-                if token.token_type == TokenType::NewLine {
-                    context.line_table.push(LineInfo {
-                        start: line_start,
-                        end: index,
-                    });
-                    line_start = index;
+            '/' => match skip_comment(&mut cursor) {
+                true => {
+                    if keep_trivia {
+                        let end = cursor.peek().map_or(text.len(), |(next, _)| *next);
+                        add_trivia(&mut trivia, TriviaKind::Comment, index, end);
+                    }
+                    continue;
                 }
-
-                token
-            }
+                false => Token::new_single(TokenType::Invalid, index),
+            },
+            '.' => match cursor.peek() {
+                Some((_, next)) if next.is_alphabetic() => get_directive(&mut cursor, index),
+                _ => Token::new_single(TokenType::Invalid, index),
+            },
+            '"' => get_string(&mut cursor, index),
+            '-' => match cursor.peek() {
+                Some((_, next)) if next.is_ascii_digit() => get_number(&mut cursor, index),
+                _ => Token::new_single(TokenType::Invalid, index),
+            },
             c if c.is_ascii_digit() => get_number(&mut cursor, index),
-            c if c.is_alphabetic() => get_symbol(&mut cursor, index),
-            c if c.is_whitespace() => continue,
-            _ => Token::new_single(TokenType::Invalid, index),
+            c if c.is_alphabetic() => get_symbol(&mut cursor, index, c),
+            c if c.is_whitespace() => {
+                if keep_trivia {
+                    add_trivia(&mut trivia, TriviaKind::Whitespace, index, index + c.len_utf8());
+                }
+                continue;
+            }
+            c => Token::new_multi(TokenType::Invalid, index, index + c.len_utf8()),
         };
 
-        tokens.push(token);
+        tokens.push(LosslessToken {
+            token,
+            leading: std::mem::take(&mut trivia),
+        });
     }
 
     context.line_table.push(LineInfo {
         start: line_start,
-        end: counter,
+        end: text.len(),
     });
 
-    tokens.push(Token::new_single(TokenType::End, text.len()));
+    tokens.push(LosslessToken {
+        token: Token::new_single(TokenType::End, text.len()),
+        leading: trivia,
+    });
     tokens
 }
 
-fn check_for_comment<I>(cursor: &mut Peekable<I>, position: usize) -> Token
+/// Original text of lossless tokens
+///
+/// # Example
+/// ```
+/// # use asim::{rebuild, tokenize_lossless};
+/// let text = "start:\tLDA  #1 // load\r\n\tJMP start\r\n";
+///
+/// assert_eq!(rebuild(&tokenize_lossless(text), text), text);
+/// ```
+pub fn rebuild(tokens: &[LosslessToken], text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    for token in tokens {
+        for trivia in &token.leading {
+            output += trivia.resolve(text);
+        }
+        if token.token.token_type != TokenType::End {
+            output += token.token.resolve(text);
+        }
+    }
+    output
+}
+
+// Consecutive whitespace is one piece of trivia
+fn add_trivia(trivia: &mut Vec<Trivia>, kind: TriviaKind, start: usize, end: usize) {
+    match trivia.last_mut() {
+        Some(last) if last.kind == kind && kind == TriviaKind::Whitespace && last.end == start => {
+            last.end = end;
+        }
+        _ => trivia.push(Trivia { kind, start, end }),
+    }
+}
+
+// Skips a `//` comment up to the end of the line, the new line itself is kept
+// Returns false if the slash does not start a comment
+fn skip_comment<I>(cursor: &mut Peekable<I>) -> bool
 where
     I: Iterator<Item = (usize, char)>,
 {
-    if let Some((_, next)) = cursor.peek() {
-        if *next == '/' {
-            cursor.next();
-            let mut new_line_pos = position + 1;
-            while let Some((index, c)) = cursor.next() {
-                new_line_pos = index;
-                if c == '\n' {
-                    break;
-                }
-            }
-            return Token::new_single(TokenType::NewLine, new_line_pos);
-        }
+    if cursor.next_if(|(_, next)| *next == '/').is_none() {
+        return false;
     }
-    Token::new_single(TokenType::Invalid, position)
+    while cursor.next_if(|(_, c)| *c != '\n').is_some() {}
+    true
 }
 
-fn get_symbol<I>(cursor: &mut Peekable<I>, start: usize) -> Token
+fn get_symbol<I>(cursor: &mut Peekable<I>, start: usize, first: char) -> Token
 where
     I: Iterator<Item = (usize, char)>,
 {
-    let mut end = start;
-
-    loop {
-        if let Some((index, c)) = cursor.peek() {
-            println!("Char {:?}\n index: {}", c, index);
-            match c {
-                '_' => {end = *index},
-                c if c.is_alphabetic() => {end = *index},
-                _ => {
-                    end = *index;
-                    break;
-                }
-            }
-            cursor.next();
-        } else {
-            end += 1;
-            break;
-        }
+    let mut end = start + first.len_utf8();
+
+    while let Some((index, c)) = cursor.next_if(|(_, c)| *c == '_' || c.is_alphanumeric()) {
+        end = index + c.len_utf8();
     }
 
     Token::new_multi(TokenType::Symbol, start, end)
 }
 
-fn get_number<I>(cursor: &mut Peekable<I>, start: usize) -> Token
+// Directives like `.equ` start with a dot followed by letters
+fn get_directive<I>(cursor: &mut Peekable<I>, start: usize) -> Token
 where
     I: Iterator<Item = (usize, char)>,
 {
-    let mut end = start;
-
-    while let Some((index, c)) = cursor.peek() {
-        match c {
-            c if c.is_ascii_digit() => (),
-            _ => {
-                end = *index;
-                break;
-            }
+    let mut end = start + 1;
+
+    while let Some((index, c)) = cursor.next_if(|(_, c)| c.is_alphanumeric()) {
+        end = index + c.len_utf8();
+    }
+
+    Token::new_multi(TokenType::Directive, start, end)
+}
+
+// Quoted text like the path of `.include`, the quotes are part of the token
+// A string without closing quote is invalid
+fn get_string<I>(cursor: &mut Peekable<I>, start: usize) -> Token
+where
+    I: Iterator<Item = (usize, char)>,
+{
+    let mut end = start + 1;
+
+    while let Some((index, c)) = cursor.next_if(|(_, c)| *c != '\n') {
+        end = index + c.len_utf8();
+        if c == '"' {
+            return Token::new_multi(TokenType::String, start, end);
         }
-        cursor.next();
     }
-    if start == end {
-        return Token::new_multi(TokenType::Number, start, start + 1);
+
+    Token::new_multi(TokenType::Invalid, start, end)
+}
+
+// The first character is a digit or the minus sign of a negative number
+//
+// Letters are part of the number to allow prefixes like `0x`, the digits are
+// validated by the resolver.
+fn get_number<I>(cursor: &mut Peekable<I>, start: usize) -> Token
+where
+    I: Iterator<Item = (usize, char)>,
+{
+    let mut end = start + 1;
+
+    while let Some((index, c)) = cursor.next_if(|(_, c)| c.is_alphanumeric()) {
+        end = index + c.len_utf8();
     }
 
     Token::new_multi(TokenType::Number, start, end)
@@ -1,6 +1,7 @@
 use crate::frontend::ParseContext;
 
-use super::{tokenize, TokenType};
+use super::token::TriviaKind;
+use super::{lex, rebuild, tokenize, tokenize_lossless, TokenType};
 
 #[test]
 fn test_tokenize() {
@@ -90,3 +91,129 @@ fn test_tokenize_empty() {
         assert_eq!(t.token_type, token_expected_token[index])
     }
 }
+
+#[test]
+fn test_tokenize_symbol_with_digits() {
+    let token_test_code: &str = "L3: JMP L3";
+
+    let token_expected_token: [TokenType; 5] = [
+        TokenType::Symbol,
+        TokenType::Colon,
+        TokenType::Symbol,
+        TokenType::Symbol,
+        TokenType::End,
+    ];
+
+    let text = String::from(token_test_code);
+    let mut context = ParseContext::new_empty(&text);
+    let token = tokenize(&text, &mut context);
+    assert_eq!(token.len(), token_expected_token.len());
+
+    for (index, t) in token.iter().enumerate() {
+        assert_eq!(t.token_type, token_expected_token[index])
+    }
+    assert_eq!(token[3].resolve(&text), "L3");
+}
+
+#[test]
+fn test_tokenize_negative_number() {
+    let token_test_code: &str = "ADD #-3 - 4";
+
+    let token_expected_token: [TokenType; 6] = [
+        TokenType::Symbol,
+        TokenType::Hash,
+        TokenType::Number,
+        TokenType::Invalid,
+        TokenType::Number,
+        TokenType::End,
+    ];
+
+    let text = String::from(token_test_code);
+    let mut context = ParseContext::new_empty(&text);
+    let token = tokenize(&text, &mut context);
+    assert_eq!(token.len(), token_expected_token.len());
+
+    for (index, t) in token.iter().enumerate() {
+        assert_eq!(t.token_type, token_expected_token[index])
+    }
+    assert_eq!(token[2].resolve(&text), "-3");
+}
+
+#[test]
+fn test_tokenize_directive() {
+    let token_test_code: &str = "x .equ 3 . 4";
+
+    let token_expected_token: [TokenType; 6] = [
+        TokenType::Symbol,
+        TokenType::Directive,
+        TokenType::Number,
+        TokenType::Invalid,
+        TokenType::Number,
+        TokenType::End,
+    ];
+
+    let text = String::from(token_test_code);
+    let mut context = ParseContext::new_empty(&text);
+    let token = tokenize(&text, &mut context);
+    assert_eq!(token.len(), token_expected_token.len());
+
+    for (index, t) in token.iter().enumerate() {
+        assert_eq!(t.token_type, token_expected_token[index])
+    }
+    assert_eq!(token[1].resolve(&text), ".equ");
+}
+
+#[test]
+fn test_tokenize_string() {
+    let token_test_code: &str = ".include \"lib/a b.asm\" // \"comment\"\n\"open";
+
+    let token_expected_token: [TokenType; 5] = [
+        TokenType::Directive,
+        TokenType::String,
+        TokenType::NewLine,
+        TokenType::Invalid,
+        TokenType::End,
+    ];
+
+    let text = String::from(token_test_code);
+    let mut context = ParseContext::new_empty(&text);
+    let token = tokenize(&text, &mut context);
+    assert_eq!(token.len(), token_expected_token.len());
+
+    for (index, t) in token.iter().enumerate() {
+        assert_eq!(t.token_type, token_expected_token[index])
+    }
+    assert_eq!(token[1].resolve(&text), "\"lib/a b.asm\"");
+    assert_eq!(token[3].resolve(&text), "\"open");
+}
+
+#[test]
+fn test_tokenize_lossless() {
+    let text = "// header\r\nstart:\tLDA  # 1 // load\r\n  ä $ /\n\n  JMP start   ";
+
+    let mut context = ParseContext::new_empty(text);
+    let lossless = tokenize_lossless(text, &mut context);
+    assert_eq!(rebuild(&lossless, text), text);
+
+    let mut context = ParseContext::new_empty(text);
+    let tokens: Vec<_> = lossless.iter().map(|token| token.token.clone()).collect();
+    assert_eq!(tokens, tokenize(text, &mut context));
+
+    // The plain tokens collect no trivia
+    let mut context = ParseContext::new_empty(text);
+    assert!(lex(text, &mut context, false).iter().all(|token| token.leading.is_empty()));
+
+    // Comments end at the new line, a carriage return in front of it is part of the comment
+    let trivia: Vec<(TriviaKind, &str)> = lossless[0]
+        .leading
+        .iter()
+        .map(|trivia| (trivia.kind, trivia.resolve(text)))
+        .collect();
+    assert_eq!(trivia, [(TriviaKind::Comment, "// header\r")]);
+    assert_eq!(lossless[0].token.token_type, TokenType::NewLine);
+    assert_eq!(lossless[3].leading[0].resolve(text), "\t");
+
+    let end = lossless.last().unwrap();
+    assert_eq!(end.token.token_type, TokenType::End);
+    assert_eq!(end.leading[0].resolve(text), "   ");
+}
@@ -1,18 +1,28 @@
+/// Kind of a token
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) enum TokenType {
+pub enum TokenType {
+    /// Instruction, label, constant or macro name
     Symbol,
+    /// Name starting with a dot like `.equ`
+    Directive,
     Number,
+    /// Text in double quotes, the quotes are part of the token
+    String,
     Hash,
     Colon,
+    Equals,
     ParenthesisOpen,
     ParenthesisClose,
+    /// Character that does not start any other token
     Invalid,
     NewLine,
+    /// Empty token at the end of the text
     End,
 }
 
+/// Piece of the source code, `start` and `end` are byte offsets into the text
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) struct Token {
+pub struct Token {
     pub token_type: TokenType,
     pub start: usize,
     pub end: usize,
@@ -47,7 +57,43 @@ impl Token {
         }
     }
 
-    pub(crate) fn resolve<'a>(&self, text: &'a str) -> &'a str {
+    /// Text of the token
+    pub fn resolve<'a>(&self, text: &'a str) -> &'a str {
         &text[self.start..self.end]
     }
 }
+
+/// Text between tokens that does not change the meaning of the program
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TriviaKind {
+    /// Spaces, tabs and carriage returns, new lines are tokens
+    Whitespace,
+    /// `//` comment up to the end of the line
+    Comment,
+}
+
+/// Whitespace or comment, `start` and `end` are byte offsets into the text
+#[derive(Debug, PartialEq, Clone)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Trivia {
+    /// Text of the trivia
+    pub fn resolve<'a>(&self, text: &'a str) -> &'a str {
+        &text[self.start..self.end]
+    }
+}
+
+/// Token together with the trivia in front of it
+///
+/// Created by [`tokenize_lossless`](crate::tokenize_lossless), the parser works on
+/// plain tokens. The trivia at the end of the text belongs to the `End` token, so
+/// the tokens cover every byte of the text.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LosslessToken {
+    pub token: Token,
+    pub leading: Vec<Trivia>,
+}
@@ -1,24 +1,41 @@
-use std::{collections::HashMap, usize};
+use std::collections::HashMap;
 
-use crate::error::{ParseErrorReport, ParseErrorReportBuilder};
+use crate::error::{ParseErrorBuilder, ParseErrorReport, ParseErrorReportBuilder, ParseErrorType};
+use analyzer::analyze;
+use expander::expand_macros;
 use lexer::tokenize;
 use parser::parse_token;
 use resolver::resolve;
+pub(crate) use source::{NoLoader, SourceMap};
+pub use formatter::format_source;
+pub use lexer::rebuild;
+pub use lexer::token::{LosslessToken, Token, TokenType, Trivia, TriviaKind};
+pub use session::{AnalysisSession, TextEdit, TextPosition};
+pub use source::{FileLoader, SourceLoader};
+pub use symbols::{SymbolKind, SymbolOccurrence, SymbolTable};
+use symbols::collect_symbols;
 
-use crate::commands::Command;
+use crate::program::Program;
 
+mod analyzer;
+mod expander;
+mod formatter;
 mod lexer;
 mod parser;
 mod resolver;
+mod session;
+mod source;
+mod suggest;
+mod symbols;
 
 #[cfg(test)]
 mod test;
 
 #[allow(unused)]
-/// Translate a string to an executable list of commands
+/// Translate a string to an executable program
 ///
 /// This function takes a str containing source code, tokenizes it, parses the tokens,
-/// and resolves them into executable commands.
+/// and resolves them into executable commands and the initial register contents.
 /// If errors occur an list of error will be returned after all steps has been done.
 ///
 /// # Example
@@ -29,18 +46,203 @@ mod test;
 /// STA (1)
 /// ";
 ///
-/// let commands = compile(text).unwrap();
+/// let program = compile(text).unwrap();
+/// assert_eq!(program.commands.len(), 2);
 /// ```
-pub fn compile(text: &str) -> Result<Vec<Command>, ParseErrorReport> {
-    let mut context = ParseContext::new_empty(text);
+pub fn compile(text: &str) -> Result<Program, ParseErrorReport> {
+    compile_with(text, &CompileOptions::default())
+}
+
+/// Translate a string to an executable program for a specific processor
+///
+/// Works like [`compile`], but checks the operands against the given options.
+/// The text is not read from a file, so every `.include` is reported as
+/// [`IncludeNotFound`](crate::ParseErrorType::IncludeNotFound). Use
+/// [`compile_with_loader`] with [`FileLoader`] to read included files.
+///
+/// # Example
+/// ```
+/// # use asim::{compile_with, CompileOptions};
+/// let options = CompileOptions {
+///     register_count: 4,
+///     ..Default::default()
+/// };
+///
+/// assert!(compile_with("STA (3)", &options).is_ok());
+/// assert!(compile_with("STA (4)", &options).is_err());
+/// ```
+pub fn compile_with(
+    text: &str,
+    options: &CompileOptions,
+) -> Result<Program, ParseErrorReport> {
+    compile_source(SourceMap::load("", text, &NoLoader), options)
+}
+
+/// Translate a file and the files it includes to an executable program
+///
+/// The loader provides the content of every file, starting with `path`.
+/// Errors name the file they occurred in through [`Line::file`](crate::Line::file).
+///
+/// # Example
+/// ```
+/// # use std::collections::HashMap;
+/// # use asim::{compile_with_loader, CompileOptions};
+/// let mut files = HashMap::new();
+/// files.insert("main.asm".to_string(), ".include \"lib/macros.asm\"\nclear 2".to_string());
+/// files.insert("lib/macros.asm".to_string(), ".macro clear r\nLDA #0\nSTA (r)\n.endm".to_string());
+///
+/// let program = compile_with_loader("main.asm", &files, &CompileOptions::default()).unwrap();
+/// assert_eq!(program.commands.len(), 2);
+///
+/// let error = compile_with_loader("missing.asm", &files, &CompileOptions::default()).unwrap_err();
+/// assert_eq!(error.files, ["missing.asm"]);
+/// ```
+pub fn compile_with_loader(
+    path: &str,
+    loader: &dyn SourceLoader,
+    options: &CompileOptions,
+) -> Result<Program, ParseErrorReport> {
+    compile_source(load_sources(path, loader), options)
+}
+
+/// Finds the labels, constants and macros of a file and the files it includes
+///
+/// The source does not have to compile, names are collected from every line that
+/// could be parsed.
+///
+/// # Example
+/// ```
+/// # use std::collections::HashMap;
+/// # use asim::{find_symbols, SymbolKind};
+/// let mut files = HashMap::new();
+/// files.insert("main.asm".to_string(), "loop: ADD #1\n    JMP loop".to_string());
+///
+/// let table = find_symbols("main.asm", &files);
+/// let usage = table.find_at(0, 1, 8).unwrap();
+/// assert_eq!((usage.name.as_str(), usage.kind), ("loop", SymbolKind::Label));
+///
+/// let definition = table.definitions_of(usage).next().unwrap();
+/// assert_eq!((definition.line, definition.start, definition.end), (0, 0, 4));
+/// ```
+pub fn find_symbols(path: &str, loader: &dyn SourceLoader) -> SymbolTable {
+    let sources = load_sources(path, loader);
+    let mut context = ParseContext::new(&sources);
+    let tokens = tokenize(&sources.text, &mut context);
+    let statements = parse_token(&tokens, &mut context);
+    collect_symbols(&statements, &context, &sources)
+}
+
+/// Splits source code into tokens that keep the whitespace and comments in front of them
+///
+/// Unlike the compiler, this never fails: characters that start no token become
+/// [`TokenType::Invalid`]. The tokens cover every byte of the text, so tools like
+/// formatters can change single tokens and [`rebuild`] the rest unchanged.
+///
+/// # Example
+/// ```
+/// # use asim::{rebuild, tokenize_lossless, TokenType, TriviaKind};
+/// let text = "start:\tLDA #1 // load\r\n\tJMP start\r\n";
+/// let tokens = tokenize_lossless(text);
+/// assert_eq!(rebuild(&tokens, text), text);
+///
+/// // The comment and the tab belong to the tokens behind them
+/// let new_line = &tokens[5];
+/// assert_eq!(new_line.token.token_type, TokenType::NewLine);
+/// let trivia: Vec<_> = new_line
+///     .leading
+///     .iter()
+///     .map(|trivia| (trivia.kind, trivia.resolve(text)))
+///     .collect();
+/// assert_eq!(
+///     trivia,
+///     [(TriviaKind::Whitespace, " "), (TriviaKind::Comment, "// load\r")]
+/// );
+/// assert_eq!(tokens[6].leading[0].resolve(text), "\t");
+/// ```
+pub fn tokenize_lossless(text: &str) -> Vec<LosslessToken> {
+    lexer::tokenize_lossless(text, &mut ParseContext::new_empty(text))
+}
+
+// A missing first file is reported like a missing include
+fn load_sources(path: &str, loader: &dyn SourceLoader) -> SourceMap {
+    match loader.load(path) {
+        Ok(text) => SourceMap::load(path, &text, loader),
+        Err(_) => {
+            let mut sources = SourceMap::load(path, "", loader);
+            sources
+                .errors
+                .push(ParseErrorBuilder::new(ParseErrorType::IncludeNotFound, 0, 0));
+            sources
+        }
+    }
+}
+
+fn compile_source(
+    sources: SourceMap,
+    options: &CompileOptions,
+) -> Result<Program, ParseErrorReport> {
+    compile_tokens(sources, options, tokenize)
+}
+
+// `lex` creates the tokens of the combined text and fills the line table
+fn compile_tokens<F>(
+    mut sources: SourceMap,
+    options: &CompileOptions,
+    lex: F,
+) -> Result<Program, ParseErrorReport>
+where
+    F: FnOnce(&str, &mut ParseContext) -> Vec<Token>,
+{
+    let errors = std::mem::take(&mut sources.errors);
+    let text = sources.text.as_str();
+    let mut context = ParseContext::new(&sources);
+    context.options = *options;
+    for error in errors {
+        context.errors.add(error);
+    }
+
+    let tokens = lex(text, &mut context);
+    let statements = parse_token(&tokens, &mut context);
+    let statements = expand_macros(&statements, &mut context);
+    let program = resolve(&statements, &mut context);
+    if context.errors.is_successful() {
+        analyze(&statements, &program.commands, &mut context);
+    }
+
+    let report = context.errors.build(text, &context.line_table, &sources);
+    finish(program, report)
+}
+
+// The program is only returned without errors, the warnings are part of it
+fn finish(mut program: Program, report: ParseErrorReport) -> Result<Program, ParseErrorReport> {
+    match report.errors.is_empty() {
+        true => {
+            program.diagnostics = report;
+            Ok(program)
+        }
+        false => Err(report),
+    }
+}
 
-    let tokens = tokenize(text, &mut context);
-    let mut command_builder = parse_token(&tokens, &mut context);
-    let commands = resolve(&mut command_builder, &mut context);
+/// Properties of the target processor that are checked while compiling
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompileOptions {
+    /// Number of registers that can be addressed with `(n)`
+    pub register_count: usize,
+    /// Whether `BRZ`, `BRC` and `BRN` may jump to a previous command
+    ///
+    /// Offsets stay signed without backward branches, the processor reads the operand
+    /// as 4-bit two's complement, so `8` to `15` would still jump back. Forward
+    /// branches are limited to 7 commands either way.
+    pub backward_branches: bool,
+}
 
-    match context.errors.is_successful() {
-        true => Ok(commands),
-        false => Err(context.errors.build(text, &context.line_table)),
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            register_count: 16,
+            backward_branches: true,
+        }
     }
 }
 
@@ -55,37 +257,52 @@ impl LineTable {
         self.0.push(value);
     }
 
+    // Lines are sorted and follow each other, so the first line ending at or behind
+    // the position contains it
     pub fn get_line_index_of(&self, position: usize) -> usize {
-        for (index, line) in self.0.iter().enumerate() {
-            if position >= line.start && position <= line.end {
-                return index;
-            }
-        }
-        self.0.len()
+        self.0.partition_point(|line| line.end < position)
     }
 
-    pub fn get_line_of(&self, position: usize) -> (usize, usize) {
-        for line in &self.0 {
-            if position >= line.start && position <= (line.end + 1) {
-                return (line.start, line.end);
-            }
-        }
-        (0, 0)
+    pub fn get_line_of(&self, position: usize) -> LineInfo {
+        self.0
+            .get(self.get_line_index_of(position))
+            .or(self.0.last())
+            .copied()
+            .unwrap_or(LineInfo { start: 0, end: 0 })
     }
 }
 
+/// Byte range of a line, `end` points at the new line character or the end of the text
 #[derive(Debug, Clone, Copy)]
 pub(crate) struct LineInfo {
     pub start: usize,
     pub end: usize,
 }
 
+/// Value of a name in the symbol table
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Symbol {
+    /// Index of the command following the label
+    Label(usize),
+    /// Value defined with `.equ`
+    Constant(isize),
+}
+
 #[derive(Debug)]
 pub(crate) struct ParseContext<'a> {
     pub errors: ParseErrorReportBuilder,
     pub line_table: LineTable,
-    pub labels: HashMap<&'a str, usize>,
+    pub labels: HashMap<&'a str, Symbol>,
+    /// Labels of macro bodies by expansion and name
+    pub local_labels: HashMap<(usize, &'a str), Symbol>,
+    /// Defined macros and their number of parameters
+    pub macros: HashMap<&'a str, usize>,
+    /// Number of commands in the program, known once the labels are collected
+    pub command_count: usize,
     pub text: &'a str,
+    pub options: CompileOptions,
+    /// Files the text was combined from, `None` for a single text
+    pub sources: Option<&'a SourceMap>,
 }
 
 impl <'a>ParseContext<'a> {
@@ -94,7 +311,31 @@ impl <'a>ParseContext<'a> {
             errors: ParseErrorReportBuilder::new(),
             line_table: LineTable::new(),
             labels: HashMap::new(),
-            text
+            local_labels: HashMap::new(),
+            macros: HashMap::new(),
+            command_count: 0,
+            text,
+            options: CompileOptions::default(),
+            sources: None,
+        }
+    }
+
+    pub fn new(sources: &'a SourceMap) -> Self {
+        Self {
+            sources: Some(sources),
+            ..Self::new_empty(&sources.text)
+        }
+    }
+
+    /// File and line inside of that file that contain the position
+    pub fn origin_of(&self, position: usize) -> (usize, usize) {
+        let line = self.line_table.get_line_index_of(position);
+        match self.sources {
+            Some(sources) => {
+                let origin = sources.origin(line);
+                (origin.file, origin.line)
+            }
+            None => (0, line),
         }
     }
 }
@@ -1,22 +1,24 @@
 use super::{operant::Operant, Token};
 
-#[derive(Debug, PartialEq)]
-pub(crate) struct CommandBuilder<'a> {
-    pub(crate) label: Option<&'a Token>,
-    pub(crate) instruction: &'a Token,
-    pub(crate) operant: Option<Operant<'a>>,
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct CommandBuilder {
+    pub(crate) label: Option<Token>,
+    pub(crate) instruction: Token,
+    pub(crate) operant: Option<Operant>,
+    pub(crate) breakpoint: bool,
 }
 
-impl<'a> CommandBuilder<'a> {
+impl CommandBuilder {
     pub(crate) fn new(
-        label: Option<&'a Token>,
-        instruction: &'a Token,
-        operant: Option<Operant<'a>>,
+        label: Option<&Token>,
+        instruction: &Token,
+        operant: Option<Operant>,
     ) -> Self {
         Self {
-            label,
-            instruction,
+            label: label.cloned(),
+            instruction: instruction.clone(),
             operant,
+            breakpoint: false,
         }
     }
-}
+}
\ No newline at end of file
@@ -5,19 +5,21 @@ use super::{
 use command_builder::CommandBuilder;
 
 use crate::{
-    commands::{get_instruction_attribute, InstructionAttribute},
+    commands::{get_instruction_attribute, InstructionAttribute, MNEMONICS},
     error::{ParseErrorBuilder, ParseErrorType},
 };
 use operant::{Operant, OperantKind};
+use statement::{
+    ConstantBuilder, DataBuilder, IncludeBuilder, MacroBuilder, MacroCallBuilder, Statement,
+};
+use super::suggest::closest_match;
 use std::iter::Peekable;
 
 pub mod command_builder;
 pub mod operant;
-pub fn parse_token<'a>(
-    tokens: &'a Vec<Token>,
-    context: &mut ParseContext,
-) -> Vec<CommandBuilder<'a>> {
-    let mut commands: Vec<CommandBuilder> = vec![];
+pub mod statement;
+pub fn parse_token(tokens: &[Token], context: &mut ParseContext) -> Vec<Statement> {
+    let mut statements: Vec<Statement> = vec![];
     let mut cursor = tokens.iter().peekable();
 
     while let Some(peek) = cursor.peek() {
@@ -25,19 +27,39 @@ pub fn parse_token<'a>(
             break;
         }
         skip_empty_lines(&mut cursor);
-        if let Some(command) = parse_line(&mut cursor, context) {
-            commands.push(command);
-            finish_line(&mut cursor, context);
-        } else {
-            recover_to_next_line(&mut cursor);
-            skip_empty_lines(&mut cursor);
+        match parse_statement(&mut cursor, context) {
+            Some(statement) => statements.push(statement),
+            None => skip_empty_lines(&mut cursor),
+        }
+    }
+    statements
+}
+
+/// Parses the statement at the cursor and moves to the end of its line
+///
+/// A statement can span several lines, like a macro definition. `None` if the
+/// statement could not be parsed, the errors are added to the context.
+pub(super) fn parse_statement<'a, I>(
+    cursor: &mut Peekable<I>,
+    context: &mut ParseContext,
+) -> Option<Statement>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    match parse_line(cursor, context) {
+        Some(statement) => {
+            finish_line(cursor, context);
+            Some(statement)
+        }
+        None => {
+            recover_to_next_line(cursor);
+            None
         }
     }
-    commands
 }
 
 // Skips empty line until next is not new line
-fn skip_empty_lines<'a, I>(cursor: &mut Peekable<I>)
+pub(super) fn skip_empty_lines<'a, I>(cursor: &mut Peekable<I>)
 where
     I: Iterator<Item = &'a Token>,
 {
@@ -90,15 +112,29 @@ where
     }
 }
 
-fn parse_line<'a, I>(
-    cursor: &mut Peekable<I>,
-    context: &mut ParseContext,
-) -> Option<CommandBuilder<'a>>
+fn parse_line<'a, I>(cursor: &mut Peekable<I>, context: &mut ParseContext) -> Option<Statement>
 where
     I: Iterator<Item = &'a Token>,
 {
-    // Check if first token is a symbol
     let first_token = cursor.next()?;
+    if first_token.token_type == TokenType::Directive {
+        let directive = first_token.resolve(context.text).to_lowercase();
+        return match directive.as_str() {
+            ".data" => parse_data(cursor, context),
+            ".macro" => parse_macro(cursor, context, first_token),
+            ".include" => parse_include(cursor, context),
+            _ => {
+                context.errors.add(ParseErrorBuilder::new(
+                    ParseErrorType::InvalidDirective,
+                    first_token.start,
+                    first_token.end,
+                ));
+                None
+            }
+        };
+    }
+
+    // Check if first token is a symbol
     first_token.ensure_type(TokenType::Symbol, || {
         if first_token.token_type != TokenType::End {
             context.errors.add(ParseErrorBuilder::new(
@@ -109,9 +145,15 @@ where
         }
     })?;
 
+    if cursor.peek()?.token_type == TokenType::Directive {
+        return parse_directive(cursor, context, first_token);
+    }
+
+    let (first_token, breakpoint) = parse_breakpoint_marker(cursor, context, first_token)?;
+
     // check if second symbol is an colon
     let second_token = cursor.peek()?;
-    Some(match second_token.token_type {
+    let mut statement = match second_token.token_type {
         TokenType::Colon => {
             cursor.next();
             skip_empty_lines(cursor);
@@ -123,13 +165,267 @@ where
                     third_token.end,
                 ));
             })?;
+            let (third_token, label_breakpoint) =
+                parse_breakpoint_marker(cursor, context, third_token)?;
 
-            parse_command(cursor, context, Some(first_token), third_token)?
+            let mut statement =
+                parse_instruction(cursor, context, Some(first_token), third_token)?;
+            statement.set_breakpoint(label_breakpoint);
+            statement
         }
-        _ => {
-            parse_command(cursor, context, None, first_token)?
+        _ => parse_instruction(cursor, context, None, first_token)?,
+    };
+    if breakpoint {
+        statement.set_breakpoint(true);
+    }
+    Some(statement)
+}
+
+// Parses a command or the call of a previously defined macro
+fn parse_instruction<'a, I>(
+    cursor: &mut Peekable<I>,
+    context: &mut ParseContext,
+    label: Option<&'a Token>,
+    instruction: &'a Token,
+) -> Option<Statement>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    let name = instruction.resolve(context.text);
+    let Some(&parameter_count) = context.macros.get(name) else {
+        return Some(Statement::Command(parse_command(
+            cursor,
+            context,
+            label,
+            instruction,
+        )?));
+    };
+
+    let mut arguments = vec![];
+    while let Some(next) = cursor.next_if(|next| {
+        !matches!(next.token_type, TokenType::NewLine | TokenType::End)
+    }) {
+        arguments.push(ensure_value(next, context)?.clone());
+    }
+    if arguments.len() != parameter_count {
+        context.errors.add(ParseErrorBuilder::new(
+            ParseErrorType::MacroArgumentCount,
+            instruction.start,
+            instruction.end,
+        ));
+        return None;
+    }
+
+    Some(Statement::MacroCall(MacroCallBuilder {
+        label: label.cloned(),
+        name: instruction.clone(),
+        arguments,
+        breakpoint: false,
+    }))
+}
+
+// Parses `.macro name parameters`, the following lines up to `.endm` are the body
+fn parse_macro<'a, I>(
+    cursor: &mut Peekable<I>,
+    context: &mut ParseContext,
+    directive: &'a Token,
+) -> Option<Statement>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    let name = cursor.next()?;
+    name.ensure_type(TokenType::Symbol, || {
+        context.errors.add(ParseErrorBuilder::new(
+            ParseErrorType::MissingLabel,
+            name.start,
+            name.end,
+        ));
+    })?;
+
+    let mut parameters = vec![];
+    while let Some(next) = cursor.next_if(|next| {
+        !matches!(next.token_type, TokenType::NewLine | TokenType::End)
+    }) {
+        next.ensure_type(TokenType::Symbol, || {
+            context.errors.add(ParseErrorBuilder::new(
+                ParseErrorType::InvalidOperant,
+                next.start,
+                next.end,
+            ));
+        })?;
+        parameters.push(next.clone());
+    }
+
+    let mut body = vec![];
+    loop {
+        skip_empty_lines(cursor);
+        let next = *cursor.peek()?;
+        match next.token_type {
+            TokenType::End => {
+                context.errors.add(ParseErrorBuilder::new(
+                    ParseErrorType::MissingMacroEnd,
+                    directive.start,
+                    directive.end,
+                ));
+                return None;
+            }
+            TokenType::Directive if next.resolve(context.text).eq_ignore_ascii_case(".endm") => {
+                cursor.next();
+                break;
+            }
+            TokenType::Directive if next.resolve(context.text).eq_ignore_ascii_case(".macro") => {
+                // Definitions can not be nested
+                context.errors.add(ParseErrorBuilder::new(
+                    ParseErrorType::InvalidDirective,
+                    next.start,
+                    next.end,
+                ));
+                recover_to_next_line(cursor);
+            }
+            _ => match parse_line(cursor, context) {
+                Some(statement) => {
+                    body.push(statement);
+                    finish_line(cursor, context);
+                }
+                None => recover_to_next_line(cursor),
+            },
         }
-    })
+    }
+
+    // The name is known after the body, so a macro can not call itself
+    let identifier = name.resolve(context.text);
+    if context.macros.contains_key(identifier) {
+        context.errors.add(ParseErrorBuilder::new(
+            ParseErrorType::MacroReassign,
+            name.start,
+            name.end,
+        ));
+        return None;
+    }
+    context.macros.insert(identifier, parameters.len());
+
+    Some(Statement::Macro(MacroBuilder {
+        name: name.clone(),
+        parameters,
+        body,
+    }))
+}
+
+// Parses `.data address = value`
+fn parse_data<'a, I>(cursor: &mut Peekable<I>, context: &mut ParseContext) -> Option<Statement>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    let address = ensure_directive_value(cursor.next()?, context)?;
+    let equals = cursor.next()?;
+    equals.ensure_type(TokenType::Equals, || {
+        context.errors.add(ParseErrorBuilder::new(
+            ParseErrorType::MissingEquals,
+            equals.start,
+            equals.end,
+        ));
+    })?;
+    let value = ensure_directive_value(cursor.next()?, context)?;
+
+    Some(Statement::Data(DataBuilder {
+        address: address.clone(),
+        value: value.clone(),
+    }))
+}
+
+// Parses `.include "path"`
+fn parse_include<'a, I>(cursor: &mut Peekable<I>, context: &mut ParseContext) -> Option<Statement>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    let path = cursor.next()?;
+    path.ensure_type(TokenType::String, || {
+        context.errors.add(ParseErrorBuilder::new(
+            ParseErrorType::MissingPath,
+            path.start,
+            path.end,
+        ));
+    })?;
+
+    Some(Statement::Include(IncludeBuilder { path: path.clone() }))
+}
+
+// Parses `name .equ value`
+fn parse_directive<'a, I>(
+    cursor: &mut Peekable<I>,
+    context: &mut ParseContext,
+    name: &'a Token,
+) -> Option<Statement>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    let directive = cursor.next()?;
+    if !directive.resolve(context.text).eq_ignore_ascii_case(".equ") {
+        context.errors.add(ParseErrorBuilder::new(
+            ParseErrorType::InvalidDirective,
+            directive.start,
+            directive.end,
+        ));
+        return None;
+    }
+
+    let value = cursor.next()?;
+    value.ensure_type(TokenType::Number, || {
+        let kind = match value.token_type {
+            TokenType::NewLine | TokenType::End => ParseErrorType::MissingOperant,
+            _ => ParseErrorType::InvalidOperant,
+        };
+        context.errors.add(ParseErrorBuilder::new(kind, value.start, value.end));
+    })?;
+
+    Some(Statement::Constant(ConstantBuilder {
+        name: name.clone(),
+        value: value.clone(),
+    }))
+}
+
+// Values of directives are numbers or the names of constants
+fn ensure_directive_value<'a>(token: &'a Token, context: &mut ParseContext) -> Option<&'a Token> {
+    match token.token_type {
+        TokenType::NewLine | TokenType::End => {
+            context.errors.add(ParseErrorBuilder::new(
+                ParseErrorType::MissingOperant,
+                token.start,
+                token.end,
+            ));
+            None
+        }
+        _ => ensure_value(token, context),
+    }
+}
+
+// Skips a `BRK` marker in front of an instruction, `BRK:` is a label
+// Returns the token after the marker and whether a marker was found
+fn parse_breakpoint_marker<'a, I>(
+    cursor: &mut Peekable<I>,
+    context: &mut ParseContext,
+    token: &'a Token,
+) -> Option<(&'a Token, bool)>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    let is_label = cursor
+        .peek()
+        .is_some_and(|next| next.token_type == TokenType::Colon);
+    if is_label || !token.resolve(context.text).eq_ignore_ascii_case("BRK") {
+        return Some((token, false));
+    }
+
+    skip_empty_lines(cursor);
+    let next_token = cursor.next()?;
+    next_token.ensure_type(TokenType::Symbol, || {
+        context.errors.add(ParseErrorBuilder::new(
+            ParseErrorType::MissingInstruction,
+            token.start,
+            token.end,
+        ));
+    })?;
+    Some((next_token, true))
 }
 
 fn parse_command<'a, I>(
@@ -137,7 +433,7 @@ fn parse_command<'a, I>(
     context: &mut ParseContext,
     label: Option<&'a Token>,
     instruction: &'a Token,
-) -> Option<CommandBuilder<'a>>
+) -> Option<CommandBuilder>
 where
     I: Iterator<Item = &'a Token>,
 {
@@ -146,16 +442,28 @@ where
     ) {
         Some(attr) => attr,
         None => {
-            context.errors.add(ParseErrorBuilder::new(
-                ParseErrorType::InvalidInstruction,
-                instruction.start,
-                instruction.end,
-            ));
+            // Macros are instructions as well
+            let name = instruction.resolve(context.text);
+            let candidates = MNEMONICS.into_iter().chain(context.macros.keys().copied());
+            context.errors.add(
+                ParseErrorBuilder::new(
+                    ParseErrorType::InvalidInstruction,
+                    instruction.start,
+                    instruction.end,
+                )
+                .suggest(closest_match(name, candidates)),
+            );
             return None;
         }
     };
 
     if attributes.allow_no_operant() {
+        // `NOP #n` stores `n` in the operand of the machine code
+        if cursor.next_if(|next| next.token_type == TokenType::Hash).is_some() {
+            let operant = parse_operant_fixed(cursor, context)?;
+            return Some(CommandBuilder::new(label, instruction, Some(operant)));
+        }
+        warn_ignored_operant(cursor, context);
         return Some(CommandBuilder::new(label, instruction, None));
     }
 
@@ -164,12 +472,30 @@ where
     Some(CommandBuilder::new(label, instruction, Some(operant)))
 }
 
+// A single number or name behind `NOP` looks like an operand but has no effect
+// Other tokens are left to `finish_line`, which reports them as errors
+fn warn_ignored_operant<'a, I>(cursor: &mut Peekable<I>, context: &mut ParseContext)
+where
+    I: Iterator<Item = &'a Token>,
+{
+    let Some(operant) = cursor.next_if(|next| {
+        matches!(next.token_type, TokenType::Number | TokenType::Symbol)
+    }) else {
+        return;
+    };
+    let kind = match cursor.peek().map(|next| &next.token_type) {
+        Some(TokenType::NewLine | TokenType::End) | None => ParseErrorType::IgnoredOperant,
+        _ => ParseErrorType::InvalidToken,
+    };
+    context.errors.add(ParseErrorBuilder::new(kind, operant.start, operant.end));
+}
+
 fn parse_operant<'a, I>(
     cursor: &mut Peekable<I>,
     context: &mut ParseContext,
     instruction: &'a Token,
     attributes: &InstructionAttribute,
-) -> Option<Operant<'a>>
+) -> Option<Operant>
 where
     I: Iterator<Item = &'a Token>,
 {
@@ -208,7 +534,7 @@ where
             }
             Operant {
                 kind: OperantKind::Fixed,
-                value: first_token,
+                value: first_token.clone(),
             }
         }
         TokenType::Symbol => {
@@ -222,7 +548,7 @@ where
             }
             Operant {
                 kind: OperantKind::Label,
-                value: first_token,
+                value: first_token.clone(),
             }
         }
         TokenType::Hash => {
@@ -250,40 +576,28 @@ where
 fn parse_operant_fixed<'a, I>(
     cursor: &mut Peekable<I>,
     context: &mut ParseContext,
-) -> Option<Operant<'a>>
+) -> Option<Operant>
 where
     I: Iterator<Item = &'a Token>,
 {
     let first_token = cursor.next()?;
-    first_token.ensure_type(TokenType::Number, || {
-        context.errors.add(ParseErrorBuilder::new(
-            ParseErrorType::InvalidOperant,
-            first_token.start,
-            first_token.end,
-        ));
-    })?;
+    ensure_value(first_token, context)?;
 
     Some(Operant {
         kind: OperantKind::Fixed,
-        value: first_token,
+        value: first_token.clone(),
     })
 }
 
 fn parse_operant_address<'a, I>(
     cursor: &mut Peekable<I>,
     context: &mut ParseContext,
-) -> Option<Operant<'a>>
+) -> Option<Operant>
 where
     I: Iterator<Item = &'a Token>,
 {
     let first_token = cursor.next()?;
-    first_token.ensure_type(TokenType::Number, || {
-        context.errors.add(ParseErrorBuilder::new(
-            ParseErrorType::InvalidOperant,
-            first_token.start,
-            first_token.end,
-        ));
-    })?;
+    ensure_value(first_token, context)?;
 
     let second_token = cursor.next()?;
     second_token.ensure_type(TokenType::ParenthesisClose, || {
@@ -295,6 +609,21 @@ where
     })?;
     Some(Operant {
         kind: OperantKind::Address,
-        value: first_token,
+        value: first_token.clone(),
     })
 }
+
+// Values are numbers or the names of constants
+fn ensure_value<'a>(token: &'a Token, context: &mut ParseContext) -> Option<&'a Token> {
+    match token.token_type {
+        TokenType::Number | TokenType::Symbol => Some(token),
+        _ => {
+            context.errors.add(ParseErrorBuilder::new(
+                ParseErrorType::InvalidOperant,
+                token.start,
+                token.end,
+            ));
+            None
+        }
+    }
+}
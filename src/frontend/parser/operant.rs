@@ -1,12 +1,12 @@
 use super::Token;
 
-#[derive(Debug, PartialEq)]
-pub struct Operant<'a> {
+#[derive(Debug, Clone, PartialEq)]
+pub struct Operant {
     pub kind: OperantKind,
-    pub value: &'a Token,
+    pub value: Token,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum OperantKind {
     Fixed,
     Address,
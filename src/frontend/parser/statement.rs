@@ -0,0 +1,93 @@
+use super::{command_builder::CommandBuilder, Token};
+
+/// Single line of the source code
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Statement {
+    Command(CommandBuilder),
+    Constant(ConstantBuilder),
+    Data(DataBuilder),
+    Macro(MacroBuilder),
+    MacroCall(MacroCallBuilder),
+    Include(IncludeBuilder),
+}
+
+impl Statement {
+    /// Marks a command as breakpoint, for a macro call the first command of the expansion
+    pub(crate) fn set_breakpoint(&mut self, breakpoint: bool) {
+        match self {
+            Statement::Command(command) => command.breakpoint = breakpoint,
+            Statement::MacroCall(call) => call.breakpoint = breakpoint,
+            _ => {}
+        }
+    }
+
+    /// Moves every token to a new position, the tokens of a macro body as well
+    pub(crate) fn move_tokens<F: Fn(usize) -> usize>(&mut self, position: &F) {
+        let mut tokens: Vec<&mut Token> = vec![];
+        match self {
+            Statement::Command(command) => {
+                tokens.extend(&mut command.label);
+                tokens.push(&mut command.instruction);
+                tokens.extend(command.operant.as_mut().map(|operant| &mut operant.value));
+            }
+            Statement::Constant(constant) => {
+                tokens.extend([&mut constant.name, &mut constant.value])
+            }
+            Statement::Data(data) => tokens.extend([&mut data.address, &mut data.value]),
+            Statement::Macro(definition) => {
+                tokens.push(&mut definition.name);
+                tokens.extend(&mut definition.parameters);
+                for statement in &mut definition.body {
+                    statement.move_tokens(position);
+                }
+            }
+            Statement::MacroCall(call) => {
+                tokens.extend(&mut call.label);
+                tokens.push(&mut call.name);
+                tokens.extend(&mut call.arguments);
+            }
+            Statement::Include(include) => tokens.push(&mut include.path),
+        }
+        for token in tokens {
+            token.start = position(token.start);
+            token.end = position(token.end);
+        }
+    }
+}
+
+/// Definition of a named value like `counter .equ 3`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ConstantBuilder {
+    pub(crate) name: Token,
+    pub(crate) value: Token,
+}
+
+/// Initial value of a register like `.data 3 = 5`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct DataBuilder {
+    pub(crate) address: Token,
+    pub(crate) value: Token,
+}
+
+/// Definition of a macro between `.macro name parameters` and `.endm`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MacroBuilder {
+    pub(crate) name: Token,
+    pub(crate) parameters: Vec<Token>,
+    pub(crate) body: Vec<Statement>,
+}
+
+/// Use of a macro, the arguments replace the parameters in the body
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MacroCallBuilder {
+    pub(crate) label: Option<Token>,
+    pub(crate) name: Token,
+    pub(crate) arguments: Vec<Token>,
+    pub(crate) breakpoint: bool,
+}
+
+/// File inserted with `.include "path"`, the content is already part of the text
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct IncludeBuilder {
+    pub(crate) path: Token,
+}
@@ -1,42 +1,168 @@
+use super::expander::ExpandedStatement;
+use super::lexer::token::{Token, TokenType};
 use super::parser::command_builder::CommandBuilder;
 use super::parser::operant::{Operant, OperantKind};
-use super::ParseContext;
+use super::parser::statement::{DataBuilder, Statement};
+use super::suggest::closest_match;
+use super::{ParseContext, Symbol};
 use crate::commands::{get_instruction_attribute, Command, Instruction, InstructionAttribute};
-use crate::error::{ParseErrorBuilder, ParseErrorType};
+use crate::error::{ParseErrorBuilder, ParseErrorReport, ParseErrorType};
+use crate::nibble::Nibble;
+use crate::program::Program;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::num::IntErrorKind;
+
+// Range of signed and unsigned values that fit into a nibble
+const SIGNED_MIN: isize = -8;
+const SIGNED_MAX: isize = 7;
+const UNSIGNED_MAX: isize = Nibble::MAX as isize;
 
 pub(super) fn resolve<'a>(
-    command_builder: &mut Vec<CommandBuilder>,
+    statements: &[ExpandedStatement],
     context: &mut ParseContext<'a>,
-) -> Vec<Command> {
-    get_label_table(command_builder, context);
-    convert_to_commands(command_builder, context)
-}
-
-fn get_label_table<'a>(command_builder: &Vec<CommandBuilder>, context: &'a mut ParseContext) {
-    for (index, command) in command_builder.iter().enumerate() {
-        if let Some(label) = command.label {
-            let label_identifier = label.resolve(context.text);
-            if let Some(_) = context.labels.get(&label_identifier) {
-                context.errors.add(ParseErrorBuilder::new(
-                    ParseErrorType::LabelReassign,
-                    label.start,
-                    label.end,
-                ));
-            } else {
-                context.labels.insert(label_identifier, index);
+) -> Program {
+    get_label_table(statements, context);
+    Program {
+        commands: convert_to_commands(statements, context),
+        files: context
+            .sources
+            .map_or_else(Vec::new, |sources| sources.files.clone()),
+        registers: get_register_image(statements, context),
+        diagnostics: ParseErrorReport::default(),
+    }
+}
+
+// Collects labels and constants, both share one namespace
+// Labels of a macro body are local to their expansion
+pub(super) fn get_label_table(statements: &[ExpandedStatement], context: &mut ParseContext) {
+    let mut index = 0;
+    for entry in statements {
+        let (name, symbol, scope) = match &entry.statement {
+            Statement::Command(command) => {
+                index += 1;
+                match &command.label {
+                    Some(label) => (label, Symbol::Label(index - 1), entry.label_scope),
+                    None => continue,
+                }
+            }
+            Statement::Constant(constant) => {
+                let overflow = ParseErrorType::ImmediateOutOfRange;
+                match resolve_number(context, &constant.value, overflow) {
+                    Ok(value) => (&constant.name, Symbol::Constant(value), None),
+                    Err(error) => {
+                        context
+                            .errors
+                            .add(error.invoked_at(entry.invocation_span()));
+                        continue;
+                    }
+                }
             }
+            _ => continue,
+        };
+        let text = context.text;
+        let defined = match scope {
+            Some(scope) => define(&mut context.local_labels, (scope, name.resolve(text)), symbol),
+            None => define(&mut context.labels, name.resolve(text), symbol),
+        };
+        if !defined {
+            context.errors.add(
+                ParseErrorBuilder::new(ParseErrorType::LabelReassign, name.start, name.end)
+                    .invoked_at(entry.invocation_span()),
+            );
         }
     }
+    context.command_count = index;
 }
 
-fn convert_to_commands<'a>(
-    command_builder: &mut Vec<CommandBuilder>,
+// Keeps the first definition, `false` if the name was already defined
+fn define<K: Hash + Eq>(table: &mut HashMap<K, Symbol>, key: K, symbol: Symbol) -> bool {
+    match table.entry(key) {
+        Entry::Occupied(_) => false,
+        Entry::Vacant(entry) => {
+            entry.insert(symbol);
+            true
+        }
+    }
+}
+
+// Initial register contents defined with `.data`
+pub(super) fn get_register_image(
+    statements: &[ExpandedStatement],
+    context: &mut ParseContext,
+) -> Vec<Nibble> {
+    let mut registers = vec![Nibble::default(); context.options.register_count];
+    let mut initialised = vec![false; context.options.register_count];
+
+    for entry in statements {
+        let Statement::Data(data) = &entry.statement else {
+            continue;
+        };
+        let result =
+            resolve_data(context, data).and_then(|(address, value)| match initialised[address] {
+                true => Err(ParseErrorBuilder::new(
+                    ParseErrorType::DataReassign,
+                    data.address.start,
+                    data.address.end,
+                )),
+                false => Ok((address, value)),
+            });
+        match result {
+            Ok((address, value)) => {
+                registers[address] = value;
+                initialised[address] = true;
+            }
+            Err(error) => context
+                .errors
+                .add(error.invoked_at(entry.invocation_span())),
+        }
+    }
+    registers
+}
+
+fn resolve_data(
+    context: &ParseContext,
+    data: &DataBuilder,
+) -> Result<(usize, Nibble), ParseErrorBuilder> {
+    let address = resolve_value(context, &data.address, ParseErrorType::AddressOutOfRange)?;
+    if address < 0 || address as usize >= context.options.register_count {
+        return Err(ParseErrorBuilder::new(
+            ParseErrorType::AddressOutOfRange,
+            data.address.start,
+            data.address.end,
+        ));
+    }
+
+    let value = resolve_value(context, &data.value, ParseErrorType::ImmediateOutOfRange)?;
+    if !(SIGNED_MIN..=UNSIGNED_MAX).contains(&value) {
+        return Err(ParseErrorBuilder::new(
+            ParseErrorType::ImmediateOutOfRange,
+            data.value.start,
+            data.value.end,
+        ));
+    }
+    Ok((
+        address as usize,
+        Nibble::from((value & UNSIGNED_MAX) as usize),
+    ))
+}
+
+fn convert_to_commands(
+    statements: &[ExpandedStatement],
     context: &mut ParseContext,
 ) -> Vec<Command> {
+    let command_builder: Vec<(&CommandBuilder, &ExpandedStatement)> = statements
+        .iter()
+        .filter_map(|entry| match &entry.statement {
+            Statement::Command(command) => Some((command, entry)),
+            _ => None,
+        })
+        .collect();
     let mut commands: Vec<Command> = Vec::with_capacity(command_builder.len());
 
-    for command in command_builder {
-        match resolve_command(context, command) {
+    for (index, (command, entry)) in command_builder.into_iter().enumerate() {
+        match resolve_entry(context, command, entry, index) {
             Ok(command) => commands.push(command),
             Err(error) => context.errors.add(error),
         }
@@ -44,9 +170,34 @@ fn convert_to_commands<'a>(
     commands
 }
 
-fn resolve_command<'a>(
-    context: &'a mut ParseContext,
+/// Resolves the command of an expanded statement, `index` is its position in the program
+pub(super) fn resolve_entry(
+    context: &mut ParseContext,
+    command: &CommandBuilder,
+    entry: &ExpandedStatement,
+    index: usize,
+) -> Result<Command, ParseErrorBuilder> {
+    let mut result = resolve_command(context, command, entry.operant_scope, index)
+        .map_err(|error| error.invoked_at(entry.invocation_span()))?;
+    (result.file, result.line) = command_origin(context, command, entry);
+    Ok(result)
+}
+
+/// File and line of a command, commands of a macro belong to the line of the call
+pub(super) fn command_origin(
+    context: &ParseContext,
+    command: &CommandBuilder,
+    entry: &ExpandedStatement,
+) -> (usize, usize) {
+    let token = entry.invocation.as_ref().unwrap_or(&command.instruction);
+    context.origin_of(token.start)
+}
+
+fn resolve_command(
+    context: &mut ParseContext,
     command: &CommandBuilder,
+    scope: Option<usize>,
+    index: usize,
 ) -> Result<Command, ParseErrorBuilder> {
     let instruction_name = command.instruction.resolve(context.text).to_uppercase();
     let attributes =
@@ -59,31 +210,39 @@ fn resolve_command<'a>(
     };
 
     let operant: usize = match &command.operant {
-        Some(operant) => resolve_operant(context, operant)?,
+        Some(operant) => {
+            let value = match resolve_operant(context, operant, scope)? {
+                // Branches are relative to their own position
+                Symbol::Label(target) if instruction != Instruction::JMP => {
+                    target as isize - index as isize
+                }
+                Symbol::Label(target) => target as isize,
+                Symbol::Constant(value) => value,
+            };
+            encode_operant(context, instruction, operant, value)?
+        }
         None => 0,
     };
 
-    let line = context
-        .line_table
-        .get_line_index_of(command.instruction.start);
-
-    Ok(Command::new(instruction, operant, line))
+    let mut result = Command::new(instruction, operant, 0);
+    result.breakpoint = command.breakpoint;
+    Ok(result)
 }
 
-fn resolve_instruction_without_operant<'a>(
+fn resolve_instruction_without_operant(
     command: &CommandBuilder,
     attributes: InstructionAttribute,
     instruction: &str,
 ) -> Result<Instruction, ParseErrorBuilder> {
     if attributes.allow_no_operant() {
-        return match instruction {
+        match instruction {
             "NOP" => Ok(Instruction::NOP),
             _ => Err(ParseErrorBuilder::new(
                 ParseErrorType::MissingOperant,
                 command.instruction.start,
                 command.instruction.end,
             )),
-        };
+        }
     } else {
         Err(ParseErrorBuilder::new(
             ParseErrorType::MissingOperant,
@@ -93,7 +252,7 @@ fn resolve_instruction_without_operant<'a>(
     }
 }
 
-fn resolve_instruction_with_operant<'a>(
+fn resolve_instruction_with_operant(
     instruction: &str,
     operant: &Operant,
 ) -> Result<Instruction, ParseErrorBuilder> {
@@ -109,6 +268,11 @@ fn resolve_instruction_with_operant<'a>(
 
 fn match_instruction(instruction: &str, operant: &Operant) -> Option<Instruction> {
     match instruction {
+        "NOP" => match operant.kind {
+            OperantKind::Fixed => Some(Instruction::NOP),
+            OperantKind::Address => None,
+            OperantKind::Label => None,
+        },
         "LDA" => match operant.kind {
             OperantKind::Fixed => Some(Instruction::LoadFix),
             OperantKind::Address => Some(Instruction::LoadFromRegister),
@@ -137,45 +301,182 @@ fn match_instruction(instruction: &str, operant: &Operant) -> Option<Instruction
         "BRZ" => match operant.kind {
             OperantKind::Fixed => Some(Instruction::BRZ),
             OperantKind::Address => None,
-            OperantKind::Label => None,
+            OperantKind::Label => Some(Instruction::BRZ),
         },
         "BRC" => match operant.kind {
             OperantKind::Fixed => Some(Instruction::BRC),
             OperantKind::Address => None,
-            OperantKind::Label => None,
+            OperantKind::Label => Some(Instruction::BRC),
         },
         "BRN" => match operant.kind {
             OperantKind::Fixed => Some(Instruction::BRN),
             OperantKind::Address => None,
-            OperantKind::Label => None,
+            OperantKind::Label => Some(Instruction::BRN),
         },
         _ => None,
     }
 }
 
+// `scope` is the expansion whose local labels the operand refers to
 fn resolve_operant(
-    context: &mut ParseContext,
+    context: &ParseContext,
     operant: &Operant,
-) -> Result<usize, ParseErrorBuilder> {
-    Ok(match operant.kind {
-        OperantKind::Fixed | OperantKind::Address => operant
-            .value
-            .resolve(context.text)
-            .parse::<usize>()
-            .map_err(|_| {
-                ParseErrorBuilder::new(
-                    ParseErrorType::InvalidOperant,
-                    operant.value.start,
-                    operant.value.end,
-                )
-            })?,
-        OperantKind::Label => *context
-            .labels
-            .get(operant.value.resolve(context.text))
-            .ok_or(ParseErrorBuilder::new(
-                ParseErrorType::MissingLabel,
+    scope: Option<usize>,
+) -> Result<Symbol, ParseErrorBuilder> {
+    if operant.value.token_type == TokenType::Number {
+        let overflow = match operant.kind {
+            OperantKind::Address => ParseErrorType::AddressOutOfRange,
+            _ => ParseErrorType::ImmediateOutOfRange,
+        };
+        return resolve_number(context, &operant.value, overflow).map(Symbol::Constant);
+    }
+
+    let symbol = lookup(context, operant.value.resolve(context.text), scope)
+        .ok_or_else(|| missing_label(context, &operant.value))?;
+    match (symbol, &operant.kind) {
+        // `#label` and `(label)` would use the position of a command as a value
+        (Symbol::Label(_), OperantKind::Fixed | OperantKind::Address) => {
+            Err(ParseErrorBuilder::new(
+                ParseErrorType::NotAllowedLabel,
                 operant.value.start,
                 operant.value.end,
-            ))?,
+            ))
+        }
+        (symbol, _) => Ok(symbol),
+    }
+}
+
+/// Label or constant with the name, `scope` is the expansion whose local labels are searched
+pub(super) fn lookup(context: &ParseContext, name: &str, scope: Option<usize>) -> Option<Symbol> {
+    match scope {
+        Some(scope) => context.local_labels.get(&(scope, name)),
+        None => context.labels.get(name),
+    }
+    .copied()
+}
+
+// Resolves a number or the name of a constant
+fn resolve_value(
+    context: &ParseContext,
+    token: &Token,
+    overflow: ParseErrorType,
+) -> Result<isize, ParseErrorBuilder> {
+    if token.token_type == TokenType::Number {
+        return resolve_number(context, token, overflow);
+    }
+    match context.labels.get(token.resolve(context.text)) {
+        Some(Symbol::Constant(value)) => Ok(*value),
+        Some(Symbol::Label(_)) => Err(ParseErrorBuilder::new(
+            ParseErrorType::NotAllowedLabel,
+            token.start,
+            token.end,
+        )),
+        None => Err(missing_label(context, token)),
+    }
+}
+
+// Unknown names are compared with the labels and constants for a suggestion
+fn missing_label(context: &ParseContext, token: &Token) -> ParseErrorBuilder {
+    let name = token.resolve(context.text);
+    ParseErrorBuilder::new(ParseErrorType::MissingLabel, token.start, token.end)
+        .suggest(closest_match(name, context.labels.keys().copied()))
+}
+
+// Numbers too large for an isize are reported with the `overflow` error
+fn resolve_number(
+    context: &ParseContext,
+    token: &Token,
+    overflow: ParseErrorType,
+) -> Result<isize, ParseErrorBuilder> {
+    parse_number(token.resolve(context.text)).map_err(|error| {
+        let kind = match error {
+            IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => overflow,
+            _ => ParseErrorType::InvalidNumber,
+        };
+        ParseErrorBuilder::new(kind, token.start, token.end)
     })
 }
+
+// Parses a decimal number or a number with one of the prefixes `0x`, `0b` and `0o`
+fn parse_number(text: &str) -> Result<isize, IntErrorKind> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let prefix = digits.get(..2).map(str::to_ascii_lowercase);
+    let (radix, digits) = match prefix.as_deref() {
+        Some("0x") => (16, &digits[2..]),
+        Some("0b") => (2, &digits[2..]),
+        Some("0o") => (8, &digits[2..]),
+        _ => (10, digits),
+    };
+    // `from_str_radix` would accept a second sign
+    if !digits.chars().all(|c| c.is_digit(radix)) {
+        return Err(IntErrorKind::InvalidDigit);
+    }
+    let number = match negative {
+        true => isize::from_str_radix(&format!("-{}", digits), radix),
+        false => isize::from_str_radix(digits, radix),
+    };
+    number.map_err(|error| *error.kind())
+}
+
+// Checks the range of the operand and stores negative values as 4-bit two's complement
+//
+// Immediates may be written signed or unsigned, branch offsets are always signed
+// and addresses have to name an existing register. Jumps may land directly behind
+// the last command to end the program.
+fn encode_operant(
+    context: &ParseContext,
+    instruction: Instruction,
+    operant: &Operant,
+    value: isize,
+) -> Result<usize, ParseErrorBuilder> {
+    let kind = match instruction {
+        Instruction::NOP | Instruction::LoadFix | Instruction::AddFix | Instruction::SubFix
+            if (SIGNED_MIN..=UNSIGNED_MAX).contains(&value) =>
+        {
+            return Ok((value & UNSIGNED_MAX) as usize)
+        }
+        Instruction::BRZ | Instruction::BRC | Instruction::BRN
+            if value < 0 && !context.options.backward_branches =>
+        {
+            ParseErrorType::BackwardBranchNotAllowed
+        }
+        Instruction::BRZ | Instruction::BRC | Instruction::BRN
+            if (SIGNED_MIN..=SIGNED_MAX).contains(&value) =>
+        {
+            return Ok((value & UNSIGNED_MAX) as usize)
+        }
+        Instruction::BRZ | Instruction::BRC | Instruction::BRN
+            if operant.kind == OperantKind::Label =>
+        {
+            ParseErrorType::BranchOutOfRange
+        }
+        Instruction::NOP
+        | Instruction::LoadFix
+        | Instruction::AddFix
+        | Instruction::SubFix
+        | Instruction::BRZ
+        | Instruction::BRC
+        | Instruction::BRN => ParseErrorType::ImmediateOutOfRange,
+        Instruction::LoadFromRegister
+        | Instruction::SaveToRegister
+        | Instruction::AddFromRegister
+        | Instruction::SubFromRegister
+            if value < 0 || value as usize >= context.options.register_count =>
+        {
+            ParseErrorType::AddressOutOfRange
+        }
+        Instruction::JMP if value < 0 => ParseErrorType::InvalidOperant,
+        Instruction::JMP if value > UNSIGNED_MAX || value as usize > context.command_count => {
+            ParseErrorType::AddressOutOfRange
+        }
+        _ => return Ok(value as usize),
+    };
+    Err(ParseErrorBuilder::new(
+        kind,
+        operant.value.start,
+        operant.value.end,
+    ))
+}
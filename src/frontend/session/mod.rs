@@ -0,0 +1,699 @@
+use super::analyzer::analyze;
+use super::expander::{ExpandedStatement, Expander};
+use super::lexer::token::{Token, TokenType};
+use super::lexer::tokenize;
+use super::parser::command_builder::CommandBuilder;
+use super::parser::statement::Statement;
+use super::parser::{parse_statement, parse_token, skip_empty_lines};
+use super::resolver::{command_origin, get_label_table, get_register_image, lookup, resolve_entry};
+use super::symbols::collect_symbols;
+use super::{
+    compile_source, finish, CompileOptions, LineInfo, NoLoader, ParseContext, SourceMap, Symbol,
+    SymbolTable,
+};
+use crate::commands::Command;
+use crate::error::{ParseErrorBuilder, ParseErrorReport, ParseErrorType};
+use crate::program::Program;
+use std::collections::HashSet;
+
+/// Line and byte column in a text, both start at 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct TextPosition {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl TextPosition {
+    pub fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+}
+
+/// Replaces the text between `start` and `end` with `text`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextEdit {
+    pub start: TextPosition,
+    pub end: TextPosition,
+    pub text: String,
+}
+
+impl TextEdit {
+    pub fn new(start: TextPosition, end: TextPosition, text: &str) -> Self {
+        Self {
+            start,
+            end,
+            text: text.to_string(),
+        }
+    }
+}
+
+/// Tokens of a single line, the positions start at the beginning of the line
+#[derive(Debug, Clone)]
+struct LexedLine {
+    length: usize,
+    tokens: Vec<Token>,
+    /// Error of an `.include`, the session does not read files
+    include: Option<ParseErrorBuilder>,
+    /// Whether the line changed since its statement was parsed
+    changed: bool,
+}
+
+/// Statement of one or more lines together with everything derived from it
+#[derive(Debug, Clone)]
+struct Block {
+    line: usize,
+    lines: usize,
+    /// `None` if the statement could not be parsed
+    statement: Option<Statement>,
+    errors: Vec<ParseErrorBuilder>,
+    /// Statements after the macro expansion, `None` until expanded
+    expansion: Option<Expansion>,
+    /// Commands of the expansion, `None` until resolved
+    commands: Option<Vec<ResolvedCommand>>,
+}
+
+impl Block {
+    // Moves all positions in the text, the line numbers are moved by the caller
+    fn move_positions<F: Fn(usize) -> usize>(&mut self, position: &F) {
+        if let Some(statement) = &mut self.statement {
+            statement.move_tokens(position);
+        }
+        let mut errors: Vec<&mut ParseErrorBuilder> = self.errors.iter_mut().collect();
+        if let Some(expansion) = &mut self.expansion {
+            for entry in &mut expansion.statements {
+                entry.statement.move_tokens(position);
+                if let Some(invocation) = &mut entry.invocation {
+                    invocation.start = position(invocation.start);
+                    invocation.end = position(invocation.end);
+                }
+            }
+            errors.extend(&mut expansion.errors);
+        }
+        if let Some(commands) = &mut self.commands {
+            errors.extend(
+                commands
+                    .iter_mut()
+                    .filter_map(|command| command.result.as_mut().err()),
+            );
+        }
+        for error in errors {
+            error.move_to(position);
+        }
+    }
+
+    // Whether the statement has to be parsed again because the named macros changed
+    fn uses(
+        &self,
+        macros: &HashSet<String>,
+        lines: &[LexedLine],
+        starts: &[usize],
+        text: &str,
+    ) -> bool {
+        if macros.is_empty() {
+            return false;
+        }
+        // Misspelled instructions are compared with all macros
+        let suggests = self
+            .errors
+            .iter()
+            .any(|error| error.kind() == ParseErrorType::InvalidInstruction);
+        suggests
+            || (self.line..self.line + self.lines).any(|line| {
+                lines[line].tokens.iter().any(|token| {
+                    token.token_type == TokenType::Symbol
+                        && macros
+                            .contains(&text[starts[line] + token.start..starts[line] + token.end])
+                })
+            })
+    }
+}
+
+/// Expanded statements of a block, the scopes of local labels start at 0
+#[derive(Debug, Clone, Default)]
+struct Expansion {
+    statements: Vec<ExpandedStatement>,
+    errors: Vec<ParseErrorBuilder>,
+    /// Number of expansions, the scopes of the following blocks are moved by it
+    scopes: usize,
+}
+
+/// Command together with everything its resolution read outside of the statement
+#[derive(Debug, Clone)]
+struct ResolvedCommand {
+    inputs: Inputs,
+    result: Result<Command, ParseErrorBuilder>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Inputs {
+    /// Label or constant of the operand, `Some(None)` for an unknown name
+    symbol: Option<Option<Symbol>>,
+    /// Position of the command, branches to labels are relative to it
+    index: Option<usize>,
+    /// Number of commands, jumps may not leave the program
+    command_count: Option<usize>,
+}
+
+impl Inputs {
+    fn of(
+        context: &ParseContext,
+        command: &CommandBuilder,
+        entry: &ExpandedStatement,
+        index: usize,
+    ) -> Self {
+        let symbol = command
+            .operant
+            .as_ref()
+            .filter(|operant| operant.value.token_type == TokenType::Symbol)
+            .map(|operant| {
+                lookup(
+                    context,
+                    operant.value.resolve(context.text),
+                    entry.operant_scope,
+                )
+            });
+        let jump = command
+            .instruction
+            .resolve(context.text)
+            .eq_ignore_ascii_case("JMP");
+        Self {
+            index: matches!(symbol, Some(Some(Symbol::Label(_)))).then_some(index),
+            command_count: jump.then_some(context.command_count),
+            symbol,
+        }
+    }
+}
+
+/// Source code of an editor that is analysed again after every change
+///
+/// The session keeps the tokens of every line, the statements with their parse
+/// errors, the expanded macro calls, the resolved commands and the names of the
+/// labels and constants. An edit tokenizes the lines it touches again, the next
+/// request of a result parses only the changed lines and the statements that use a
+/// changed macro, expands only the calls of changed macros and resolves only the
+/// commands whose labels, constants or position changed. The register image and
+/// the warnings are computed from the kept parts for every result.
+///
+/// The result is always the same as [`compile_with`](crate::compile_with) of the
+/// current text returns. A text whose last line is an `.include` is compiled as a
+/// whole, the include adds a line to it.
+///
+/// # Example
+/// ```
+/// # use asim::{compile, AnalysisSession, TextEdit, TextPosition};
+/// let mut session = AnalysisSession::new("start: LDA #1\n    JMP strat");
+/// assert!(session.result().is_err());
+///
+/// let edit = TextEdit::new(TextPosition::new(1, 8), TextPosition::new(1, 13), "start");
+/// session.edit(&edit);
+/// assert_eq!(session.text(), "start: LDA #1\n    JMP start");
+/// assert_eq!(*session.result(), compile("start: LDA #1\n    JMP start"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct AnalysisSession {
+    text: String,
+    options: CompileOptions,
+    lines: Vec<LexedLine>,
+    /// Statements in the order of the text, the lines between them are empty
+    blocks: Vec<Block>,
+    /// Macros whose definitions were removed since the statements were parsed
+    changed_macros: HashSet<String>,
+    /// Names of the labels and constants, unknown names are compared with them
+    names: HashSet<String>,
+    /// Result of the current text, `None` after an edit
+    result: Option<Result<Program, ParseErrorReport>>,
+}
+
+impl AnalysisSession {
+    pub fn new(text: &str) -> Self {
+        Self::with_options(text, &CompileOptions::default())
+    }
+
+    /// Creates a session that checks the operands against the given options
+    pub fn with_options(text: &str, options: &CompileOptions) -> Self {
+        Self {
+            text: text.to_string(),
+            options: *options,
+            lines: text.split('\n').map(lex_line).collect(),
+            blocks: vec![],
+            changed_macros: HashSet::new(),
+            names: HashSet::new(),
+            result: None,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Whether the text has an `.include`, the session reports every one as not found
+    pub fn has_includes(&self) -> bool {
+        self.lines.iter().any(|line| line.include.is_some())
+    }
+
+    /// Applies a change of the editor
+    ///
+    /// Positions behind the end of a line or the text are moved to the end, columns
+    /// inside of a character to its start. An end in front of the start removes nothing.
+    pub fn edit(&mut self, edit: &TextEdit) {
+        let (first, start) = self.locate(edit.start);
+        let (last, end) = match self.locate(edit.end) {
+            (_, end) if end < start => (first, start),
+            located => located,
+        };
+
+        // Statements on the affected lines are parsed again
+        let text = self.text.as_str();
+        let (lines, changed_macros) = (&mut self.lines, &mut self.changed_macros);
+        self.blocks.retain(|block| {
+            if block.line > last || block.line + block.lines <= first {
+                return true;
+            }
+            for line in &mut lines[block.line..block.line + block.lines] {
+                line.changed = true;
+            }
+            if let Some(Statement::Macro(definition)) = &block.statement {
+                changed_macros.insert(definition.name.resolve(text).to_string());
+            }
+            false
+        });
+
+        // The affected lines are tokenized again, all others keep their tokens
+        let region_start = self.line_start(first);
+        let removed_end = self.line_start(last) + self.lines[last].length;
+        self.text.replace_range(start..end, &edit.text);
+        let region_end = removed_end - (end - start) + edit.text.len();
+        let lexed: Vec<LexedLine> = self.text[region_start..region_end]
+            .split('\n')
+            .map(lex_line)
+            .collect();
+        let added = lexed.len();
+        self.lines.splice(first..=last, lexed);
+
+        // Everything behind the edit moves with the text
+        let position = |position: usize| match position >= removed_end {
+            true => position - removed_end + region_end,
+            false => position,
+        };
+        for block in self.blocks.iter_mut().filter(|block| block.line > last) {
+            block.line = block.line + added - (last - first + 1);
+            block.move_positions(&position);
+        }
+        self.result = None;
+    }
+
+    /// Program or errors of the current text, like [`compile_with`](crate::compile_with)
+    pub fn result(&mut self) -> &Result<Program, ParseErrorReport> {
+        if self.result.is_none() {
+            self.result = Some(self.compile());
+        }
+        match &self.result {
+            Some(result) => result,
+            None => unreachable!("the result was just compiled"),
+        }
+    }
+
+    /// Errors and warnings of the current text
+    pub fn diagnostics(&mut self) -> &ParseErrorReport {
+        match self.result() {
+            Ok(program) => &program.diagnostics,
+            Err(report) => report,
+        }
+    }
+
+    /// Labels, constants and macros of the current text, like [`find_symbols`](crate::find_symbols)
+    pub fn symbols(&mut self) -> SymbolTable {
+        if self.ends_with_include() {
+            let sources = SourceMap::load("", &self.text, &NoLoader);
+            let mut context = ParseContext::new(&sources);
+            let tokens = tokenize(&sources.text, &mut context);
+            let statements = parse_token(&tokens, &mut context);
+            return collect_symbols(&statements, &context, &sources);
+        }
+        self.parse();
+        let sources = SourceMap::unmapped("");
+        let context = new_context(&self.text, &self.lines, &self.options, &sources);
+        let statements: Vec<Statement> = self
+            .blocks
+            .iter()
+            .filter_map(|block| block.statement.clone())
+            .collect();
+        collect_symbols(&statements, &context, &sources)
+    }
+
+    fn compile(&mut self) -> Result<Program, ParseErrorReport> {
+        // A new line is added behind an `.include` in the last line, the kept tokens do not match it
+        if self.ends_with_include() {
+            return compile_source(SourceMap::load("", &self.text, &NoLoader), &self.options);
+        }
+        self.parse();
+        self.expand();
+
+        let sources = SourceMap::unmapped("");
+        let mut context = new_context(&self.text, &self.lines, &self.options, &sources);
+        let mut start = 0;
+        for line in &self.lines {
+            if let Some(error) = &line.include {
+                let mut error = error.clone();
+                error.move_to(|position| start + position);
+                context.errors.add(error);
+            }
+            start += line.length + 1;
+        }
+        for error in self.blocks.iter().flat_map(|block| &block.errors) {
+            context.errors.add(error.clone());
+        }
+
+        // Scopes of local labels are counted through the whole program
+        let mut statements = vec![];
+        let mut scopes = 0;
+        for block in &self.blocks {
+            let expansion = block.expansion.as_ref().expect("every block is expanded");
+            for error in &expansion.errors {
+                context.errors.add(error.clone());
+            }
+            statements.extend(expansion.statements.iter().map(|entry| ExpandedStatement {
+                label_scope: entry.label_scope.map(|scope| scope + scopes),
+                operant_scope: entry.operant_scope.map(|scope| scope + scopes),
+                ..entry.clone()
+            }));
+            scopes += expansion.scopes;
+        }
+
+        get_label_table(&statements, &mut context);
+        // Unknown names suggest the most similar name, which depends on all names
+        let names_changed = context.labels.len() != self.names.len()
+            || context
+                .labels
+                .keys()
+                .any(|name| !self.names.contains(*name));
+        if names_changed {
+            self.names = context.labels.keys().map(|name| name.to_string()).collect();
+        }
+
+        let mut commands = vec![];
+        let mut index = 0;
+        let mut entries = statements.iter();
+        for block in &mut self.blocks {
+            let expansion = block.expansion.as_ref().expect("every block is expanded");
+            let cached = block.commands.take().unwrap_or_default();
+            let mut resolved = vec![];
+            for entry in entries.by_ref().take(expansion.statements.len()) {
+                let Statement::Command(command) = &entry.statement else {
+                    continue;
+                };
+                let inputs = Inputs::of(&context, command, entry, index);
+                let result = match cached.get(resolved.len()) {
+                    Some(cached)
+                        if cached.inputs == inputs
+                            && !(inputs.symbol == Some(None) && names_changed) =>
+                    {
+                        cached.result.clone()
+                    }
+                    _ => resolve_entry(&mut context, command, entry, index),
+                };
+                match &result {
+                    Ok(resolved) => {
+                        let mut resolved = *resolved;
+                        (resolved.file, resolved.line) = command_origin(&context, command, entry);
+                        commands.push(resolved);
+                    }
+                    Err(error) => context.errors.add(error.clone()),
+                }
+                resolved.push(ResolvedCommand { inputs, result });
+                index += 1;
+            }
+            block.commands = Some(resolved);
+        }
+
+        let registers = get_register_image(&statements, &mut context);
+        if context.errors.is_successful() {
+            analyze(&statements, &commands, &mut context);
+        }
+        let program = Program {
+            commands,
+            files: sources.files.clone(),
+            registers,
+            diagnostics: ParseErrorReport::default(),
+        };
+        let report = context
+            .errors
+            .build(&self.text, &context.line_table, &sources);
+        finish(program, report)
+    }
+
+    // Parses the changed lines and the statements that use a changed macro
+    // A definition that uses a changed macro changes itself, so its calls are parsed
+    // again as well
+    fn parse(&mut self) {
+        let text = self.text.as_str();
+        let lines = &mut self.lines;
+        let blocks = &mut self.blocks;
+        let changed = &mut std::mem::take(&mut self.changed_macros);
+        let starts = line_starts(lines);
+        let mut context = ParseContext::new_empty(text);
+        let mut index = 0;
+        let mut line = 0;
+
+        loop {
+            let next = blocks.get(index).map_or(lines.len(), |block| block.line);
+            if let Some(first) = (line..next).find(|&line| lines[line].changed) {
+                // The changed lines are parsed up to the next kept statement, a statement
+                // that continues into it replaces it
+                let mut start = first;
+                let mut end = next;
+                loop {
+                    let (parsed, overrun) = parse_lines(lines, &starts, start, end, &mut context);
+                    for block in parsed {
+                        add_macro(changed, &block, text);
+                        blocks.insert(index, block);
+                        index += 1;
+                    }
+                    let Some(overrun) = overrun else {
+                        break;
+                    };
+                    let removed = blocks.remove(index);
+                    add_macro(changed, &removed, text);
+                    start = overrun;
+                    end = blocks.get(index).map_or(lines.len(), |block| block.line);
+                }
+                for line in &mut lines[first..end] {
+                    line.changed = false;
+                }
+                line = end;
+                continue;
+            }
+
+            let Some(block) = blocks.get_mut(index) else {
+                break;
+            };
+            if block.uses(changed, lines, &starts, text) {
+                add_macro(changed, block, text);
+                let end = block.line + block.lines;
+                let (mut parsed, _) = parse_lines(lines, &starts, block.line, end, &mut context);
+                *block = parsed
+                    .pop()
+                    .expect("the lines of a statement are parsed again");
+                add_macro(changed, block, text);
+            } else if let Some(Statement::Macro(definition)) = &block.statement {
+                context
+                    .macros
+                    .insert(definition.name.resolve(text), definition.parameters.len());
+            }
+            line = block.line + block.lines;
+            index += 1;
+        }
+    }
+
+    // Expands the new statements, calls of a changed macro were parsed again
+    fn expand(&mut self) {
+        let text = self.text.as_str();
+        let definitions: Vec<_> = self
+            .blocks
+            .iter()
+            .filter_map(|block| match &block.statement {
+                Some(Statement::Macro(definition)) => Some(definition),
+                _ => None,
+            })
+            .collect();
+
+        let mut expander = Expander::new();
+        for definition in definitions {
+            expander.define(definition, text);
+        }
+        let mut context = ParseContext::new_empty(text);
+        let expansions: Vec<(usize, Expansion)> = self
+            .blocks
+            .iter()
+            .enumerate()
+            .filter(|(_, block)| block.expansion.is_none())
+            .map(|(index, block)| {
+                let expansion = match &block.statement {
+                    Some(Statement::MacroCall(call)) => {
+                        let (statements, scopes) = expander.expand_alone(call, &mut context);
+                        Expansion {
+                            statements,
+                            errors: std::mem::take(&mut context.errors.error),
+                            scopes,
+                        }
+                    }
+                    Some(Statement::Macro(_)) | None => Expansion::default(),
+                    Some(statement) => Expansion {
+                        statements: vec![ExpandedStatement::global(statement.clone())],
+                        ..Expansion::default()
+                    },
+                };
+                (index, expansion)
+            })
+            .collect();
+        for (index, expansion) in expansions {
+            self.blocks[index].expansion = Some(expansion);
+            self.blocks[index].commands = None;
+        }
+    }
+
+    fn ends_with_include(&self) -> bool {
+        self.lines.last().is_some_and(|line| line.include.is_some())
+    }
+
+    // Index of the line and byte offset in the text
+    fn locate(&self, position: TextPosition) -> (usize, usize) {
+        let last = self.lines.len() - 1;
+        if position.line > last {
+            return (last, self.text.len());
+        }
+        let mut offset =
+            self.line_start(position.line) + position.column.min(self.lines[position.line].length);
+        while !self.text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        (position.line, offset)
+    }
+
+    fn line_start(&self, line: usize) -> usize {
+        self.lines[..line].iter().map(|line| line.length + 1).sum()
+    }
+}
+
+// Comments and strings end at the new line, so every line can be tokenized on its own
+fn lex_line(line: &str) -> LexedLine {
+    let mut tokens = tokenize(line, &mut ParseContext::new_empty(line));
+    tokens.pop();
+    let include = match tokens.first() {
+        Some(token)
+            if token.token_type == TokenType::Directive
+                && token.resolve(line).eq_ignore_ascii_case(".include") =>
+        {
+            SourceMap::load("", line, &NoLoader).errors.pop()
+        }
+        _ => None,
+    };
+    LexedLine {
+        length: line.len(),
+        tokens,
+        include,
+        changed: true,
+    }
+}
+
+// Context of the whole text with its line table
+fn new_context<'a>(
+    text: &'a str,
+    lines: &[LexedLine],
+    options: &CompileOptions,
+    sources: &'a SourceMap,
+) -> ParseContext<'a> {
+    let mut context = ParseContext::new_empty(text);
+    context.sources = Some(sources);
+    context.options = *options;
+    let mut start = 0;
+    for line in lines {
+        let end = start + line.length;
+        context.line_table.push(LineInfo { start, end });
+        start = end + 1;
+    }
+    context
+}
+
+fn line_starts(lines: &[LexedLine]) -> Vec<usize> {
+    let mut start = 0;
+    lines
+        .iter()
+        .map(|line| {
+            let line_start = start;
+            start += line.length + 1;
+            line_start
+        })
+        .collect()
+}
+
+// Parses the statements of the lines `first..end`, `first` has to start a statement
+// Returns the statements and the first line of a statement that continues behind `end`
+fn parse_lines(
+    lines: &[LexedLine],
+    starts: &[usize],
+    first: usize,
+    end: usize,
+    context: &mut ParseContext,
+) -> (Vec<Block>, Option<usize>) {
+    let mut tokens = vec![];
+    for line in first..end {
+        let start = starts[line];
+        tokens.extend(lines[line].tokens.iter().map(|token| {
+            Token::new_multi(
+                token.token_type.clone(),
+                start + token.start,
+                start + token.end,
+            )
+        }));
+        if line + 1 < lines.len() {
+            tokens.push(Token::new_single(
+                TokenType::NewLine,
+                start + lines[line].length,
+            ));
+        }
+    }
+    // Behind the last line of the text is its end, otherwise the next line
+    let tail = end == lines.len();
+    let end_position = match tail {
+        true => context.text.len(),
+        false => starts[end],
+    };
+    tokens.push(Token::new_single(TokenType::End, end_position));
+    let line_of = |position: usize| {
+        first + starts[first..end].partition_point(|&start| start <= position) - 1
+    };
+
+    let mut cursor = tokens.iter().peekable();
+    let mut blocks = vec![];
+    loop {
+        skip_empty_lines(&mut cursor);
+        let line = match cursor.peek() {
+            Some(token) if token.token_type != TokenType::End => line_of(token.start),
+            _ => break,
+        };
+        let statement = parse_statement(&mut cursor, context);
+        let errors = std::mem::take(&mut context.errors.error);
+        let last = match cursor.peek() {
+            Some(token) if token.token_type == TokenType::NewLine => line_of(token.start),
+            _ if tail => end - 1,
+            _ => return (blocks, Some(line)),
+        };
+        blocks.push(Block {
+            line,
+            lines: last + 1 - line,
+            statement,
+            errors,
+            expansion: None,
+            commands: None,
+        });
+    }
+    (blocks, None)
+}
+
+// Records the name of a macro definition that was parsed again or removed
+fn add_macro(changed: &mut HashSet<String>, block: &Block, text: &str) {
+    if let Some(Statement::Macro(definition)) = &block.statement {
+        changed.insert(definition.name.resolve(text).to_string());
+    }
+}
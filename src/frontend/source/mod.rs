@@ -0,0 +1,216 @@
+use super::lexer::token::TokenType;
+use super::lexer::tokenize;
+use super::ParseContext;
+use crate::error::{ParseErrorBuilder, ParseErrorType};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Provides the text of the files named by `.include`
+///
+/// # Example
+/// ```
+/// # use std::collections::HashMap;
+/// # use asim::{compile_with_loader, CompileOptions};
+/// let mut files = HashMap::new();
+/// files.insert("main.asm".to_string(), ".include \"lib.asm\"\nSTA (1)".to_string());
+/// files.insert("lib.asm".to_string(), "LDA #3".to_string());
+///
+/// let program = compile_with_loader("main.asm", &files, &CompileOptions::default()).unwrap();
+/// assert_eq!(program.commands.len(), 2);
+/// ```
+pub trait SourceLoader {
+    /// Returns the content of the file, paths are relative to the including file
+    fn load(&self, path: &str) -> io::Result<String>;
+}
+
+/// Reads the included files from the file system
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileLoader;
+
+impl SourceLoader for FileLoader {
+    fn load(&self, path: &str) -> io::Result<String> {
+        std::fs::read_to_string(path)
+    }
+}
+
+/// Refuses every file, `.include` is reported as not found
+///
+/// Used for source code that is not read from a file, so compiling a string never
+/// touches the file system.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct NoLoader;
+
+impl SourceLoader for NoLoader {
+    fn load(&self, _path: &str) -> io::Result<String> {
+        Err(io::Error::from(io::ErrorKind::NotFound))
+    }
+}
+
+/// Files in memory, the key is the path
+impl SourceLoader for HashMap<String, String> {
+    fn load(&self, path: &str) -> io::Result<String> {
+        self.get(path)
+            .cloned()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+    }
+}
+
+/// Origin of a line of the combined text
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LineOrigin {
+    /// Index into the file names
+    pub file: usize,
+    /// Line inside of the file
+    pub line: usize,
+}
+
+/// All files of a program combined into a single text
+///
+/// The content of an included file is inserted after the line of its `.include`,
+/// so every token can still be addressed by a byte offset.
+#[derive(Debug)]
+pub(crate) struct SourceMap {
+    pub text: String,
+    pub files: Vec<String>,
+    pub lines: Vec<LineOrigin>,
+    pub errors: Vec<ParseErrorBuilder>,
+}
+
+impl SourceMap {
+    /// Creates a map with the text of the first file and all its includes
+    pub(crate) fn load(name: &str, text: &str, loader: &dyn SourceLoader) -> Self {
+        let mut map = Self {
+            text: String::with_capacity(text.len()),
+            files: vec![],
+            lines: vec![],
+            errors: vec![],
+        };
+        map.add_file(name, text, loader, &mut vec![resolve_path("", name)]);
+
+        // The lexer creates a line behind the last new line
+        if map.text.is_empty() || map.text.ends_with('\n') {
+            map.lines.push(LineOrigin {
+                file: 0,
+                line: text.matches('\n').count(),
+            });
+        }
+        map
+    }
+
+    /// Map of a single file without includes, the text is kept by the caller
+    ///
+    /// Every line is its own origin.
+    pub(crate) fn unmapped(name: &str) -> Self {
+        Self {
+            text: String::new(),
+            files: vec![name.to_string()],
+            lines: vec![],
+            errors: vec![],
+        }
+    }
+
+    /// Origin of the line with the given index in the combined text
+    pub(crate) fn origin(&self, line: usize) -> LineOrigin {
+        self.lines
+            .get(line)
+            .or(self.lines.last())
+            .copied()
+            .unwrap_or(LineOrigin { file: 0, line })
+    }
+
+    fn add_file(
+        &mut self,
+        name: &str,
+        text: &str,
+        loader: &dyn SourceLoader,
+        stack: &mut Vec<String>,
+    ) {
+        let file = self.files.len();
+        self.files.push(name.to_string());
+
+        for (index, line) in text.split_inclusive('\n').enumerate() {
+            let start = self.text.len();
+            self.text.push_str(line);
+            self.lines.push(LineOrigin { file, line: index });
+
+            let Some((path_start, path_end)) = find_include(line) else {
+                continue;
+            };
+            if !self.text.ends_with('\n') {
+                self.text.push('\n');
+            }
+            let path = resolve_path(name, &line[path_start + 1..path_end - 1]);
+            let (path_start, path_end) = (start + path_start, start + path_end);
+
+            if stack.contains(&path) {
+                self.errors.push(ParseErrorBuilder::new(
+                    ParseErrorType::IncludeCycle,
+                    path_start,
+                    path_end,
+                ));
+                continue;
+            }
+            let Ok(content) = loader.load(&path) else {
+                self.errors.push(ParseErrorBuilder::new(
+                    ParseErrorType::IncludeNotFound,
+                    path_start,
+                    path_end,
+                ));
+                continue;
+            };
+
+            stack.push(path.clone());
+            self.add_file(&path, &content, loader, stack);
+            stack.pop();
+            if !self.text.ends_with('\n') {
+                self.text.push('\n');
+            }
+        }
+    }
+}
+
+// Span of the quoted path if the line is an `.include` directive
+// The line is tokenized, so `//` inside of the path does not start a comment
+// Malformed directives are reported by the parser
+fn find_include(line: &str) -> Option<(usize, usize)> {
+    let tokens = tokenize(line, &mut ParseContext::new_empty(line));
+    match tokens.as_slice() {
+        [directive, path, rest @ ..]
+            if directive.token_type == TokenType::Directive
+                && directive.resolve(line).eq_ignore_ascii_case(".include")
+                && path.token_type == TokenType::String
+                && rest
+                    .iter()
+                    .all(|token| matches!(token.token_type, TokenType::NewLine | TokenType::End)) =>
+        {
+            Some((path.start, path.end))
+        }
+        _ => None,
+    }
+}
+
+// Paths are relative to the directory of the including file
+// `.` and `..` are removed so every file has a single name
+fn resolve_path(including: &str, path: &str) -> String {
+    let joined = Path::new(including)
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(path);
+
+    let mut resolved = PathBuf::new();
+    for component in joined.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match resolved.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    resolved.pop();
+                }
+                Some(Component::RootDir) => {}
+                _ => resolved.push(".."),
+            },
+            component => resolved.push(component),
+        }
+    }
+    resolved.to_string_lossy().into_owned()
+}
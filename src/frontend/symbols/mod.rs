@@ -0,0 +1,194 @@
+use super::lexer::token::{Token, TokenType};
+use super::parser::statement::Statement;
+use super::{ParseContext, SourceMap};
+use std::collections::{HashMap, HashSet};
+
+/// Names of a program with the position of every definition and use
+///
+/// Created by [`find_symbols`](crate::find_symbols) for editor features like
+/// go-to-definition. Positions follow [`ParseError`](crate::ParseError): a line
+/// of a file and the byte range inside of that line.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SymbolTable {
+    /// Names of the files, the first one includes the others
+    pub files: Vec<String>,
+    /// Occurrences in the order of the source
+    pub symbols: Vec<SymbolOccurrence>,
+}
+
+/// Definition or use of a name
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolOccurrence {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Whether the name is defined here
+    pub definition: bool,
+    /// Index into [`SymbolTable::files`]
+    pub file: usize,
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SymbolKind {
+    Label,
+    Constant,
+    Macro,
+}
+
+impl SymbolTable {
+    /// Occurrence that contains the byte `column` of the line
+    pub fn find_at(&self, file: usize, line: usize, column: usize) -> Option<&SymbolOccurrence> {
+        self.symbols.iter().find(|symbol| {
+            symbol.file == file
+                && symbol.line == line
+                && symbol.start <= column
+                && column <= symbol.end
+        })
+    }
+
+    /// Definitions of the name of the occurrence, usually exactly one
+    pub fn definitions_of<'a>(
+        &'a self,
+        symbol: &'a SymbolOccurrence,
+    ) -> impl Iterator<Item = &'a SymbolOccurrence> {
+        self.references_of(symbol).filter(|other| other.definition)
+    }
+
+    /// All occurrences of the same name including the definitions
+    pub fn references_of<'a>(
+        &'a self,
+        symbol: &'a SymbolOccurrence,
+    ) -> impl Iterator<Item = &'a SymbolOccurrence> {
+        // Labels and constants share one namespace, macros have their own
+        let macro_name = symbol.kind == SymbolKind::Macro;
+        self.symbols.iter().filter(move |other| {
+            other.name == symbol.name && (other.kind == SymbolKind::Macro) == macro_name
+        })
+    }
+
+    /// Occurrences that define a name
+    pub fn definitions(&self) -> impl Iterator<Item = &SymbolOccurrence> {
+        self.symbols.iter().filter(|symbol| symbol.definition)
+    }
+}
+
+pub(super) fn collect_symbols(
+    statements: &[Statement],
+    context: &ParseContext,
+    sources: &SourceMap,
+) -> SymbolTable {
+    let mut collector = Collector {
+        context,
+        sources,
+        kinds: HashMap::new(),
+        symbols: vec![],
+    };
+    // Definitions first, so uses in front of a definition get the right kind
+    collector.define(statements);
+    collector.collect(statements, &HashSet::new());
+
+    let mut seen = HashSet::new();
+    let mut symbols = collector.symbols;
+    symbols.sort_by_key(|symbol| (symbol.file, symbol.line, symbol.start));
+    // Files included more than once appear several times in the text
+    symbols.retain(|symbol| seen.insert((symbol.file, symbol.line, symbol.start)));
+
+    SymbolTable {
+        files: sources.files.clone(),
+        symbols,
+    }
+}
+
+struct Collector<'a, 'b> {
+    context: &'b ParseContext<'a>,
+    sources: &'b SourceMap,
+    /// Kinds of the labels and constants
+    kinds: HashMap<&'a str, SymbolKind>,
+    symbols: Vec<SymbolOccurrence>,
+}
+
+impl<'a> Collector<'a, '_> {
+    fn define(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            let (name, kind) = match statement {
+                Statement::Command(command) => match &command.label {
+                    Some(label) => (label, SymbolKind::Label),
+                    None => continue,
+                },
+                Statement::Constant(constant) => (&constant.name, SymbolKind::Constant),
+                _ => continue,
+            };
+            self.kinds
+                .entry(name.resolve(self.context.text))
+                .or_insert(kind);
+        }
+    }
+
+    // `parameters` are the names that refer to arguments inside of a macro body
+    fn collect(&mut self, statements: &[Statement], parameters: &HashSet<&str>) {
+        for statement in statements {
+            match statement {
+                Statement::Command(command) => {
+                    if let Some(label) = &command.label {
+                        self.push(label, SymbolKind::Label, true);
+                    }
+                    if let Some(operant) = &command.operant {
+                        self.push_use(&operant.value, parameters);
+                    }
+                }
+                Statement::Constant(constant) => {
+                    self.push(&constant.name, SymbolKind::Constant, true)
+                }
+                Statement::Data(data) => {
+                    self.push_use(&data.address, parameters);
+                    self.push_use(&data.value, parameters);
+                }
+                Statement::Macro(definition) => {
+                    self.push(&definition.name, SymbolKind::Macro, true);
+                    let parameters = definition
+                        .parameters
+                        .iter()
+                        .map(|parameter| parameter.resolve(self.context.text))
+                        .collect();
+                    self.collect(&definition.body, &parameters);
+                }
+                Statement::MacroCall(call) => {
+                    if let Some(label) = &call.label {
+                        self.push(label, SymbolKind::Label, true);
+                    }
+                    self.push(&call.name, SymbolKind::Macro, false);
+                    for argument in &call.arguments {
+                        self.push_use(argument, parameters);
+                    }
+                }
+                Statement::Include(_) => {}
+            }
+        }
+    }
+
+    fn push_use(&mut self, token: &Token, parameters: &HashSet<&str>) {
+        let name = token.resolve(self.context.text);
+        if token.token_type != TokenType::Symbol || parameters.contains(name) {
+            return;
+        }
+        let kind = self.kinds.get(name).copied().unwrap_or(SymbolKind::Label);
+        self.push(token, kind, false);
+    }
+
+    fn push(&mut self, token: &Token, kind: SymbolKind, definition: bool) {
+        let line_table = &self.context.line_table;
+        let origin = self.sources.origin(line_table.get_line_index_of(token.start));
+        let line_start = line_table.get_line_of(token.start).start;
+        self.symbols.push(SymbolOccurrence {
+            name: token.resolve(self.context.text).to_string(),
+            kind,
+            definition,
+            file: origin.file,
+            line: origin.line,
+            start: token.start - line_start,
+            end: token.end - line_start,
+        });
+    }
+}
@@ -1,6 +1,8 @@
 use crate::commands::{Command, Instruction};
 
-use super::compile;
+use super::{compile, compile_with, compile_with_loader, CompileOptions, SymbolKind};
+use std::collections::HashMap;
+use crate::error::ParseErrorType;
 
 #[test]
 fn test_single_line_compile() {
@@ -23,11 +25,11 @@ up:  STA (15)
         Command::new(Instruction::JMP, 0, 7),
     ];
 
-    let result = compile(&code);
+    let result = compile(code);
 
     match result {
-        Ok(commands) => {
-            assert_eq!(expected_commands, commands)
+        Ok(program) => {
+            assert_eq!(expected_commands, program.commands)
         }
         Err(err) => panic!("{:#?}", err),
     }
@@ -38,11 +40,11 @@ fn test_compile_single_line() {
     let code = "ADD #1";
     let expected_command = vec![Command::new(Instruction::AddFix, 1, 0)];
 
-    let result = compile(&code);
+    let result = compile(code);
 
     match result {
-        Ok(commands) => {
-            assert_eq!(expected_command, commands)
+        Ok(program) => {
+            assert_eq!(expected_command, program.commands)
         }
         Err(err) => panic!("{:#?}", err),
     }
@@ -53,11 +55,11 @@ fn test_empty_line_compile() {
     let code = "";
     let expected_command: Vec<Command> = vec![];
 
-    let result = compile(&code);
+    let result = compile(code);
 
     match result {
-        Ok(commands) => {
-            assert_eq!(expected_command, commands)
+        Ok(program) => {
+            assert_eq!(expected_command, program.commands)
         }
         Err(err) => panic!("{:#?}", err),
     }
@@ -70,7 +72,7 @@ ADD #2 ADD #3
 ";
 
     assert!(
-        compile(&code).is_err(),
+        compile(code).is_err(),
         "Expected compilation to fail for multi-line instructions"
     );
 }
@@ -83,15 +85,1095 @@ NOP";
     let expected_commands = vec![
         Command::new(Instruction::AddFix, 1, 1),
         Command::new(Instruction::NOP, 0, 2),
-        Command::new(Instruction::JMP, 0, 3)
     ];
 
     let result = compile(code);
 
     match result {
-        Ok(commands) => {
-            assert_eq!(expected_commands, commands)
+        Ok(program) => {
+            assert_eq!(expected_commands, program.commands)
         }
         Err(err) => panic!("{:#?}", err),
     }
 }
+
+#[test]
+fn test_compile_breakpoint_marker() {
+    let code = "
+BRK LDA #1
+start: BRK
+    STA (1)
+BRK loop: ADD #1
+    JMP loop
+";
+    let mut expected_commands = vec![
+        Command::new(Instruction::LoadFix, 1, 1),
+        Command::new(Instruction::SaveToRegister, 1, 3),
+        Command::new(Instruction::AddFix, 1, 4),
+        Command::new(Instruction::JMP, 2, 5),
+    ];
+    expected_commands[0].breakpoint = true;
+    expected_commands[1].breakpoint = true;
+    expected_commands[2].breakpoint = true;
+
+    match compile(code) {
+        Ok(program) => assert_eq!(expected_commands, program.commands),
+        Err(err) => panic!("{:#?}", err),
+    }
+}
+
+#[test]
+fn test_compile_breakpoint_label() {
+    // `BRK` directly followed by a colon is a label, not a marker
+    let code = "brk: NOP
+    JMP brk
+BRK Brk: ADD #1
+    BRK JMP Brk";
+    let mut expected_commands = vec![
+        Command::new(Instruction::NOP, 0, 0),
+        Command::new(Instruction::JMP, 0, 1),
+        Command::new(Instruction::AddFix, 1, 2),
+        Command::new(Instruction::JMP, 2, 3),
+    ];
+    expected_commands[2].breakpoint = true;
+    expected_commands[3].breakpoint = true;
+
+    match compile(code) {
+        Ok(program) => assert_eq!(expected_commands, program.commands),
+        Err(err) => panic!("{:#?}", err),
+    }
+}
+
+#[test]
+fn test_compile_breakpoint_without_instruction() {
+    assert!(compile("LDA #1\nBRK").is_err());
+}
+
+#[test]
+fn test_compile_error_position() {
+    let code = "FOO #1
+// Zähler
+LDA (3";
+
+    let errors = compile(code).unwrap_err().errors;
+    assert_eq!(errors.len(), 2, "{:#?}", errors);
+
+    assert_eq!(errors[0].line.number, 0);
+    assert_eq!(errors[0].line.value, "FOO #1");
+    assert_eq!((errors[0].start, errors[0].end), (0, 3));
+
+    assert_eq!(errors[1].line.number, 2);
+    assert_eq!(errors[1].line.value, "LDA (3");
+    assert_eq!((errors[1].start, errors[1].end), (6, 6));
+}
+
+#[test]
+fn test_compile_immediate_out_of_range() {
+    let code = "LDA #15
+ADD #16
+BRZ 17
+SUB #99999999999999999999999";
+
+    let errors = compile(code).unwrap_err().errors;
+    assert_eq!(errors.len(), 3, "{:#?}", errors);
+
+    for error in &errors {
+        assert_eq!(error.kind, ParseErrorType::ImmediateOutOfRange);
+    }
+    assert_eq!(errors[0].line.number, 1);
+    assert_eq!((errors[0].start, errors[0].end), (5, 7));
+    assert_eq!(errors[1].line.number, 2);
+    assert_eq!((errors[1].start, errors[1].end), (4, 6));
+    assert_eq!(errors[2].line.number, 3);
+    assert_eq!((errors[2].start, errors[2].end), (5, 28));
+}
+
+#[test]
+fn test_compile_address_out_of_range() {
+    let code = "LDA (15)\nSTA ( 16 )";
+
+    let errors = compile(code).unwrap_err().errors;
+    assert_eq!(errors.len(), 1, "{:#?}", errors);
+    assert_eq!(errors[0].kind, ParseErrorType::AddressOutOfRange);
+    assert_eq!((errors[0].start, errors[0].end), (6, 8));
+
+    let options = CompileOptions {
+        register_count: 8,
+        ..Default::default()
+    };
+    let errors = compile_with("ADD (7)\nSUB (8)", &options).unwrap_err().errors;
+    assert_eq!(errors.len(), 1, "{:#?}", errors);
+    assert_eq!(errors[0].kind, ParseErrorType::AddressOutOfRange);
+    assert_eq!(errors[0].line.number, 1);
+}
+
+#[test]
+fn test_compile_jump_out_of_range() {
+    // A jump directly behind the last command ends the program
+    assert!(compile("NOP\nJMP 2").is_ok());
+
+    let errors = compile("NOP\nJMP 4\nJMP 40").unwrap_err().errors;
+    let errors: Vec<(usize, usize, usize, ParseErrorType)> = errors
+        .iter()
+        .map(|error| (error.line.number, error.start, error.end, error.kind))
+        .collect();
+    assert_eq!(
+        errors,
+        [
+            (1, 4, 5, ParseErrorType::AddressOutOfRange),
+            (2, 4, 6, ParseErrorType::AddressOutOfRange),
+        ]
+    );
+}
+
+#[test]
+fn test_compile_jump_label_out_of_range() {
+    let mut code = String::from("start: NOP\n");
+    for _ in 0..16 {
+        code += "NOP\n";
+    }
+    code += "end: JMP end\nJMP start";
+
+    // `end` is the 18th command, its index does not fit into a nibble
+    let errors = compile(&code).unwrap_err().errors;
+    assert_eq!(errors.len(), 1, "{:#?}", errors);
+    assert_eq!(errors[0].kind, ParseErrorType::AddressOutOfRange);
+    assert_eq!(errors[0].line.number, 17);
+    assert_eq!((errors[0].start, errors[0].end), (9, 12));
+}
+
+#[test]
+fn test_compile_negative_operant() {
+    let code = "LDA #-3
+ADD #-8
+loop: BRZ #-2
+BRC #7";
+    let expected_commands = vec![
+        Command::new(Instruction::LoadFix, 13, 0),
+        Command::new(Instruction::AddFix, 8, 1),
+        Command::new(Instruction::BRZ, 14, 2),
+        Command::new(Instruction::BRC, 7, 3),
+    ];
+
+    match compile(code) {
+        Ok(program) => assert_eq!(expected_commands, program.commands),
+        Err(err) => panic!("{:#?}", err),
+    }
+}
+
+#[test]
+fn test_compile_negative_operant_out_of_range() {
+    let code = "LDA #-9
+BRZ #8
+BRN #-9
+STA (-1)
+JMP -1";
+
+    let errors = compile(code).unwrap_err().errors;
+    let kinds: Vec<ParseErrorType> = errors.iter().map(|error| error.kind).collect();
+    assert_eq!(
+        kinds,
+        [
+            ParseErrorType::ImmediateOutOfRange,
+            ParseErrorType::ImmediateOutOfRange,
+            ParseErrorType::ImmediateOutOfRange,
+            ParseErrorType::AddressOutOfRange,
+            ParseErrorType::InvalidOperant,
+        ]
+    );
+    assert_eq!((errors[3].start, errors[3].end), (5, 7));
+}
+
+#[test]
+fn test_compile_prefixed_numbers() {
+    let code = "LDA #0xF
+ADD #0b0101
+SUB (0o17)
+BRZ #-0x2
+STA (0B11)";
+    let expected_commands = vec![
+        Command::new(Instruction::LoadFix, 15, 0),
+        Command::new(Instruction::AddFix, 5, 1),
+        Command::new(Instruction::SubFromRegister, 15, 2),
+        Command::new(Instruction::BRZ, 14, 3),
+        Command::new(Instruction::SaveToRegister, 3, 4),
+    ];
+
+    match compile(code) {
+        Ok(program) => assert_eq!(expected_commands, program.commands),
+        Err(err) => panic!("{:#?}", err),
+    }
+}
+
+#[test]
+fn test_compile_malformed_numbers() {
+    let code = "LDA #0b102
+ADD #0x
+SUB #12a
+LDA #0x10";
+
+    let errors = compile(code).unwrap_err().errors;
+    let kinds: Vec<ParseErrorType> = errors.iter().map(|error| error.kind).collect();
+    assert_eq!(
+        kinds,
+        [
+            ParseErrorType::InvalidNumber,
+            ParseErrorType::InvalidNumber,
+            ParseErrorType::InvalidNumber,
+            ParseErrorType::ImmediateOutOfRange,
+        ]
+    );
+    assert_eq!((errors[0].start, errors[0].end), (5, 10));
+}
+
+#[test]
+fn test_compile_branch_label() {
+    let code = "
+start: LDA (0)
+    BRZ end
+    SUB #1
+    BRN start
+end: NOP";
+    let expected_commands = vec![
+        Command::new(Instruction::LoadFromRegister, 0, 1),
+        Command::new(Instruction::BRZ, 3, 2),
+        Command::new(Instruction::SubFix, 1, 3),
+        Command::new(Instruction::BRN, 0b1101, 4),
+        Command::new(Instruction::NOP, 0, 5),
+    ];
+
+    match compile(code) {
+        Ok(program) => assert_eq!(expected_commands, program.commands),
+        Err(err) => panic!("{:#?}", err),
+    }
+}
+
+#[test]
+fn test_compile_branch_label_out_of_range() {
+    let code = format!("BRZ end\n{}end: NOP", "NOP\n".repeat(8));
+
+    let errors = compile(&code).unwrap_err().errors;
+    assert_eq!(errors.len(), 1, "{:#?}", errors);
+    assert_eq!(errors[0].kind, ParseErrorType::BranchOutOfRange);
+    assert_eq!((errors[0].start, errors[0].end), (4, 7));
+}
+
+#[test]
+fn test_compile_backward_branch_not_allowed() {
+    let code = "start: NOP
+BRZ start
+BRC #-1
+BRN #2
+BRZ #8";
+    let options = CompileOptions {
+        backward_branches: false,
+        ..Default::default()
+    };
+
+    let errors = compile_with(code, &options).unwrap_err().errors;
+    let kinds: Vec<(usize, ParseErrorType)> = errors
+        .iter()
+        .map(|error| (error.line.number, error.kind))
+        .collect();
+    // `8` would be read as `-8` by the processor, so it stays out of range
+    assert_eq!(
+        kinds,
+        [
+            (1, ParseErrorType::BackwardBranchNotAllowed),
+            (2, ParseErrorType::BackwardBranchNotAllowed),
+            (4, ParseErrorType::ImmediateOutOfRange),
+        ]
+    );
+    assert_eq!(compile(code).unwrap_err().errors.len(), 1);
+}
+
+#[test]
+fn test_compile_constants() {
+    let code = "
+counter .equ 3
+step .EQU 0x1
+    LDA (counter)
+loop: SUB #step
+    STA (counter)
+    BRZ done
+    JMP loop
+done: NOP";
+    let expected_commands = vec![
+        Command::new(Instruction::LoadFromRegister, 3, 3),
+        Command::new(Instruction::SubFix, 1, 4),
+        Command::new(Instruction::SaveToRegister, 3, 5),
+        Command::new(Instruction::BRZ, 2, 6),
+        Command::new(Instruction::JMP, 1, 7),
+        Command::new(Instruction::NOP, 0, 8),
+    ];
+
+    match compile(code) {
+        Ok(program) => assert_eq!(expected_commands, program.commands),
+        Err(err) => panic!("{:#?}", err),
+    }
+}
+
+#[test]
+fn test_compile_constant_errors() {
+    let code = "value .equ 3
+value .equ 4
+start: LDA #value
+start .equ 5
+STA (start)
+LDA #missing
+big .equ 16
+ADD #big
+x .def 1
+y .equ";
+
+    let errors = compile(code).unwrap_err().errors;
+    let kinds: Vec<(usize, ParseErrorType)> = errors
+        .iter()
+        .map(|error| (error.line.number, error.kind))
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            (8, ParseErrorType::InvalidDirective),
+            (9, ParseErrorType::MissingOperant),
+            (1, ParseErrorType::LabelReassign),
+            (3, ParseErrorType::LabelReassign),
+            (4, ParseErrorType::NotAllowedLabel),
+            (5, ParseErrorType::MissingLabel),
+            (7, ParseErrorType::ImmediateOutOfRange),
+        ]
+    );
+    assert_eq!((errors[2].start, errors[2].end), (0, 5));
+}
+
+#[test]
+fn test_compile_data() {
+    let code = "
+input .equ 2
+.data input = 5
+.DATA 0xF = -1
+LDA (input)";
+
+    let program = match compile(code) {
+        Ok(program) => program,
+        Err(err) => panic!("{:#?}", err),
+    };
+    assert_eq!(
+        program.commands,
+        vec![Command::new(Instruction::LoadFromRegister, 2, 4)]
+    );
+    assert_eq!(program.registers.len(), 16);
+    let registers: Vec<u8> = program.registers.iter().map(|value| value.get_value()).collect();
+    assert_eq!(registers, [0, 0, 5, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 15]);
+}
+
+#[test]
+fn test_compile_data_errors() {
+    let code = ".data 1 = 2
+.data 1 = 3
+.data 16 = 0
+.data 0 = 16
+.data 0 3
+.org 0 = 1
+start: NOP
+.data start = 1";
+
+    let errors = compile(code).unwrap_err().errors;
+    let kinds: Vec<(usize, ParseErrorType)> = errors
+        .iter()
+        .map(|error| (error.line.number, error.kind))
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            (4, ParseErrorType::MissingEquals),
+            (5, ParseErrorType::InvalidDirective),
+            (1, ParseErrorType::DataReassign),
+            (2, ParseErrorType::AddressOutOfRange),
+            (3, ParseErrorType::ImmediateOutOfRange),
+            (7, ParseErrorType::NotAllowedLabel),
+        ]
+    );
+    assert_eq!((errors[2].start, errors[2].end), (6, 7));
+}
+
+#[test]
+fn test_compile_macro() {
+    let code = ".macro copy from to
+    LDA (from)
+    STA (to)
+.endm
+.macro clear register
+    LDA #0
+    STA (register)
+.endm
+.macro move from to
+    copy from to
+    clear from
+.endm
+    copy 1 2
+start: BRK move 3 4
+    JMP start";
+
+    let program = match compile(code) {
+        Ok(program) => program,
+        Err(err) => panic!("{:#?}", err),
+    };
+    let mut expected_commands = vec![
+        Command::new(Instruction::LoadFromRegister, 1, 12),
+        Command::new(Instruction::SaveToRegister, 2, 12),
+        Command::new(Instruction::LoadFromRegister, 3, 13),
+        Command::new(Instruction::SaveToRegister, 4, 13),
+        Command::new(Instruction::LoadFix, 0, 13),
+        Command::new(Instruction::SaveToRegister, 3, 13),
+        Command::new(Instruction::JMP, 2, 14),
+    ];
+    expected_commands[2].breakpoint = true;
+    assert_eq!(program.commands, expected_commands);
+}
+
+#[test]
+fn test_compile_macro_local_labels() {
+    let code = ".macro jump target
+    JMP target
+.endm
+.macro wait r
+    LDA (r)
+loop: SUB #1
+    BRZ done
+    jump loop
+done: STA (r)
+.endm
+start: wait 1
+    wait 2
+    JMP start";
+
+    let program = match compile(code) {
+        Ok(program) => program,
+        Err(err) => panic!("{:#?}", err),
+    };
+    // Every call jumps inside of its own expansion
+    let expected_commands = vec![
+        Command::new(Instruction::LoadFromRegister, 1, 10),
+        Command::new(Instruction::SubFix, 1, 10),
+        Command::new(Instruction::BRZ, 2, 10),
+        Command::new(Instruction::JMP, 1, 10),
+        Command::new(Instruction::SaveToRegister, 1, 10),
+        Command::new(Instruction::LoadFromRegister, 2, 11),
+        Command::new(Instruction::SubFix, 1, 11),
+        Command::new(Instruction::BRZ, 2, 11),
+        Command::new(Instruction::JMP, 6, 11),
+        Command::new(Instruction::SaveToRegister, 2, 11),
+        Command::new(Instruction::JMP, 0, 12),
+    ];
+    assert_eq!(program.commands, expected_commands);
+    assert!(program.diagnostics.warnings.is_empty());
+
+    // Labels of the body are not visible outside of the expansion
+    let code = ".macro spin
+loop: JMP loop
+.endm
+    spin
+    JMP loop";
+    let errors = compile(code).unwrap_err().errors;
+    let kinds: Vec<(usize, ParseErrorType)> = errors
+        .iter()
+        .map(|error| (error.line.number, error.kind))
+        .collect();
+    assert_eq!(kinds, [(4, ParseErrorType::MissingLabel)]);
+}
+
+#[test]
+fn test_compile_macro_errors() {
+    let code = ".macro load value
+    LDA #value
+    STA (16)
+.endm
+.macro load value
+.endm
+    load 3
+    load 16
+    load 1 2
+.macro open
+    NOP";
+
+    let errors = compile(code).unwrap_err().errors;
+    let kinds: Vec<(usize, ParseErrorType)> = errors
+        .iter()
+        .map(|error| (error.line.number, error.kind))
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            (4, ParseErrorType::MacroReassign),
+            (8, ParseErrorType::MacroArgumentCount),
+            (9, ParseErrorType::MissingMacroEnd),
+            (2, ParseErrorType::AddressOutOfRange),
+            (7, ParseErrorType::ImmediateOutOfRange),
+            (2, ParseErrorType::AddressOutOfRange),
+        ]
+    );
+
+    // Errors of the body point into the definition and name the call
+    let invocation = errors[3].invocation.as_ref().unwrap();
+    assert_eq!(invocation.name, "load");
+    assert_eq!(invocation.line.number, 6);
+    assert_eq!((invocation.start, invocation.end), (4, 8));
+    assert_eq!(errors[5].invocation.as_ref().unwrap().line.number, 7);
+    // Arguments are reported where they were written
+    assert_eq!((errors[4].start, errors[4].end), (9, 11));
+    assert!(errors[0].invocation.is_none());
+}
+
+#[test]
+fn test_compile_macro_before_definition() {
+    let code = "    clear
+.macro clear
+    LDA #0
+.endm";
+
+    let errors = compile(code).unwrap_err().errors;
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind, ParseErrorType::InvalidInstruction);
+}
+
+fn files(files: &[(&str, &str)]) -> HashMap<String, String> {
+    files
+        .iter()
+        .map(|(path, text)| (path.to_string(), text.to_string()))
+        .collect()
+}
+
+fn in_file(mut command: Command, file: usize) -> Command {
+    command.file = file;
+    command
+}
+
+#[test]
+fn test_compile_include() {
+    let files = files(&[
+        (
+            "main.asm",
+            "    LDA #1
+.include \"lib/macros.asm\" // shared code
+    clear 2
+.include \"lib/jump.asm\"",
+        ),
+        ("lib/macros.asm", ".include \"./constants.asm\"\n.macro clear r\nLDA #zero\nSTA (r)\n.endm\n"),
+        ("lib/constants.asm", "zero .equ 0"),
+        ("lib/jump.asm", "end:\n    clear 1\n    JMP end"),
+    ]);
+
+    let program = match compile_with_loader("main.asm", &files, &CompileOptions::default()) {
+        Ok(program) => program,
+        Err(err) => panic!("{:#?}", err),
+    };
+    assert_eq!(
+        program.files,
+        ["main.asm", "lib/macros.asm", "lib/constants.asm", "lib/jump.asm"]
+    );
+    // Commands keep the file and line they are written in, macros the ones of the call
+    let expected_commands = vec![
+        Command::new(Instruction::LoadFix, 1, 0),
+        Command::new(Instruction::LoadFix, 0, 2),
+        Command::new(Instruction::SaveToRegister, 2, 2),
+        in_file(Command::new(Instruction::LoadFix, 0, 1), 3),
+        in_file(Command::new(Instruction::SaveToRegister, 1, 1), 3),
+        in_file(Command::new(Instruction::JMP, 3, 2), 3),
+    ];
+    assert_eq!(program.commands, expected_commands);
+}
+
+#[test]
+fn test_compile_include_path_with_slashes() {
+    // `//` inside of the path does not start a comment
+    let files = files(&[
+        ("main.asm", ".include \"lib//a.asm\" // a\n.INCLUDE \"b.asm\"\n"),
+        ("lib/a.asm", "    LDA #3"),
+        ("b.asm", "    STA (1)"),
+    ]);
+    let program = compile_with_loader("main.asm", &files, &CompileOptions::default()).unwrap();
+    let expected_commands = vec![
+        in_file(Command::new(Instruction::LoadFix, 3, 0), 1),
+        in_file(Command::new(Instruction::SaveToRegister, 1, 0), 2),
+    ];
+    assert_eq!(program.commands, expected_commands);
+}
+
+#[test]
+fn test_compile_include_errors() {
+    let files = files(&[
+        (
+            "main.asm",
+            ".include \"a.asm\"
+.include \"missing.asm\"
+.include main.asm
+    NOP",
+        ),
+        ("a.asm", "    ADD #16\n.include \"sub/../main.asm\""),
+    ]);
+
+    let report = compile_with_loader("main.asm", &files, &CompileOptions::default()).unwrap_err();
+    assert_eq!(report.files, ["main.asm", "a.asm"]);
+    let kinds: Vec<(usize, usize, ParseErrorType)> = report
+        .errors
+        .iter()
+        .map(|error| (error.line.file, error.line.number, error.kind))
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            (1, 1, ParseErrorType::IncludeCycle),
+            (0, 1, ParseErrorType::IncludeNotFound),
+            (0, 2, ParseErrorType::MissingPath),
+            (1, 0, ParseErrorType::ImmediateOutOfRange),
+        ]
+    );
+    assert_eq!((report.errors[1].start, report.errors[1].end), (9, 22));
+
+    let report = compile_with_loader("other.asm", &files, &CompileOptions::default()).unwrap_err();
+    assert_eq!(report.files, ["other.asm"]);
+    assert_eq!(report.errors[0].kind, ParseErrorType::IncludeNotFound);
+}
+
+#[test]
+fn test_compile_include_without_loader() {
+    use super::AnalysisSession;
+
+    // The file exists, but a string is compiled without access to the file system
+    let code = ".include \"Cargo.toml\"\n    NOP";
+    let report = compile(code).unwrap_err();
+    let kinds: Vec<(usize, ParseErrorType)> = report
+        .errors
+        .iter()
+        .map(|error| (error.line.number, error.kind))
+        .collect();
+    assert_eq!(kinds, [(0, ParseErrorType::IncludeNotFound)]);
+    assert_eq!(*AnalysisSession::new(code).result(), Err(report));
+    assert!(AnalysisSession::new(code).symbols().symbols.is_empty());
+}
+
+#[test]
+fn test_compile_warnings() {
+    let code = "start: LDA (1)
+    NOP 0
+    STA (2)
+    STA (1)
+    BRZ skip
+    JMP end
+    ADD #1
+    SUB #1
+skip:
+    ADD #1
+end: NOP";
+
+    let program = match compile(code) {
+        Ok(program) => program,
+        Err(err) => panic!("{:#?}", err),
+    };
+    assert_eq!(program.commands.len(), 10);
+    assert_eq!(program.commands[1], Command::new(Instruction::NOP, 0, 1));
+
+    let warnings: Vec<(usize, usize, usize, ParseErrorType)> = program
+        .diagnostics
+        .warnings
+        .iter()
+        .map(|warning| {
+            (
+                warning.line.number,
+                warning.start,
+                warning.end,
+                warning.kind,
+            )
+        })
+        .collect();
+    assert_eq!(
+        warnings,
+        [
+            (0, 0, 5, ParseErrorType::UnusedLabel),
+            (1, 8, 9, ParseErrorType::IgnoredOperant),
+            (2, 9, 10, ParseErrorType::UnusedStore),
+            (6, 4, 10, ParseErrorType::UnreachableCode),
+        ]
+    );
+    assert!(program.diagnostics.errors.is_empty());
+}
+
+#[test]
+fn test_compile_warnings_with_errors() {
+    let code = "    NOP 1
+    LDA #16
+unused: JMP 0";
+
+    let report = compile(code).unwrap_err();
+    assert_eq!(report.errors.len(), 1);
+    // Only the parser warnings are known if the program contains errors
+    assert_eq!(report.warnings.len(), 1);
+    assert_eq!(report.warnings[0].kind, ParseErrorType::IgnoredOperant);
+    assert_eq!((report.warnings[0].start, report.warnings[0].end), (8, 9));
+}
+
+#[test]
+fn test_compile_nop_with_tokens() {
+    // A missing new line must not drop the following command
+    let report = compile("NOP LDA #3").unwrap_err();
+    let errors: Vec<(usize, ParseErrorType)> = report
+        .errors
+        .iter()
+        .map(|error| (error.start, error.kind))
+        .collect();
+    assert_eq!(
+        errors,
+        [
+            (4, ParseErrorType::InvalidToken),
+            (8, ParseErrorType::InvalidToken),
+            (9, ParseErrorType::InvalidToken),
+        ]
+    );
+    assert!(report.warnings.is_empty());
+
+    let report = compile("NOP foo bar ( #").unwrap_err();
+    assert_eq!(report.errors.len(), 4);
+    assert!(report
+        .errors
+        .iter()
+        .all(|error| error.kind == ParseErrorType::InvalidToken));
+}
+
+#[test]
+fn test_compile_nop_operant() {
+    // `#` marks an operand that is kept in the machine code
+    let code = "x .equ 3
+    NOP #x
+    NOP #-1
+    nop #0b1010";
+    let program = compile(code).unwrap();
+    let expected_commands = vec![
+        Command::new(Instruction::NOP, 3, 1),
+        Command::new(Instruction::NOP, 15, 2),
+        Command::new(Instruction::NOP, 10, 3),
+    ];
+    assert_eq!(program.commands, expected_commands);
+    assert!(program.diagnostics.warnings.is_empty());
+
+    let errors = compile("NOP #16\nNOP #start\nstart: NOP\nNOP #").unwrap_err().errors;
+    let kinds: Vec<(usize, ParseErrorType)> = errors
+        .iter()
+        .map(|error| (error.line.number, error.kind))
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            (3, ParseErrorType::InvalidOperant),
+            (0, ParseErrorType::ImmediateOutOfRange),
+            (1, ParseErrorType::NotAllowedLabel),
+        ]
+    );
+}
+
+#[test]
+fn test_compile_suggestions() {
+    let code = ".macro clear r
+    LDA #0
+    STA (r)
+.endm
+limit .equ 3
+start: NOP
+    lad #limti
+    clera 1
+    JMP strat
+    SUB #limti
+    ADD #xyz
+    BRZ s";
+
+    let errors = compile(code).unwrap_err().errors;
+    let suggestions: Vec<(ParseErrorType, Option<&str>)> = errors
+        .iter()
+        .map(|error| (error.kind, error.suggestion.as_deref()))
+        .collect();
+    assert_eq!(
+        suggestions,
+        [
+            (ParseErrorType::InvalidInstruction, Some("LDA")),
+            (ParseErrorType::InvalidInstruction, Some("clear")),
+            (ParseErrorType::MissingLabel, Some("start")),
+            (ParseErrorType::MissingLabel, Some("limit")),
+            (ParseErrorType::MissingLabel, None),
+            (ParseErrorType::MissingLabel, None),
+        ]
+    );
+}
+
+#[test]
+fn test_find_symbols() {
+    let files = files(&[
+        (
+            "main.asm",
+            ".include \"lib.asm\"
+    JMP start
+start: clear limit
+    BRZ start",
+        ),
+        (
+            "lib.asm",
+            "limit .equ 3
+.macro clear r
+    LDA #r
+    STA (limit)
+.endm",
+        ),
+    ]);
+
+    let table = super::find_symbols("main.asm", &files);
+    assert_eq!(table.files, ["main.asm", "lib.asm"]);
+    let symbols: Vec<(usize, usize, usize, &str, SymbolKind, bool)> = table
+        .symbols
+        .iter()
+        .map(|symbol| {
+            (
+                symbol.file,
+                symbol.line,
+                symbol.start,
+                symbol.name.as_str(),
+                symbol.kind,
+                symbol.definition,
+            )
+        })
+        .collect();
+    assert_eq!(
+        symbols,
+        [
+            (0, 1, 8, "start", SymbolKind::Label, false),
+            (0, 2, 0, "start", SymbolKind::Label, true),
+            (0, 2, 7, "clear", SymbolKind::Macro, false),
+            (0, 2, 13, "limit", SymbolKind::Constant, false),
+            (0, 3, 8, "start", SymbolKind::Label, false),
+            (1, 0, 0, "limit", SymbolKind::Constant, true),
+            (1, 1, 7, "clear", SymbolKind::Macro, true),
+            (1, 3, 9, "limit", SymbolKind::Constant, false),
+        ]
+    );
+
+    let usage = table.find_at(0, 2, 9).unwrap();
+    let definition = table.definitions_of(usage).next().unwrap();
+    assert_eq!((definition.file, definition.line), (1, 1));
+    assert_eq!(table.references_of(table.find_at(0, 1, 10).unwrap()).count(), 3);
+}
+
+#[test]
+fn test_format_source() {
+    let text = "// Counts down
+counter .EQU 3   // start value
+.DATA 0 = 3
+
+.macro load v
+lda #v   // inside
+.endm
+\tLDA ( counter )  // load
+start:
+    brz 4
+loop:  brk sta (1) // store
+    SUB # 1
+  // indented note
+    JMP start // back
+    load 2
+nop
+
+
+";
+    let formatted = super::format_source(text);
+    assert_eq!(
+        formatted,
+        "// Counts down
+counter .equ 3 // start value
+.data 0 = 3
+
+.macro load v
+      LDA #v        // inside
+.endm
+      LDA (counter) // load
+start:
+      BRZ 4
+loop: BRK STA (1)   // store
+      SUB #1
+      // indented note
+      JMP start     // back
+      load 2
+      NOP
+"
+    );
+    assert_eq!(super::format_source(&formatted), formatted);
+
+    let original = compile(text).unwrap();
+    let program = compile(&formatted).unwrap();
+    assert_eq!(program.commands, original.commands);
+    assert_eq!(program.registers, original.registers);
+}
+
+#[test]
+fn test_format_source_keeps_meaning() {
+    // A macro named like a mnemonic in lower case replaces the instruction
+    let text = ".macro lda v\nADD #v\n.endm\nlda 1\nLda #1\nbrk .equ 2\nSTA (brk)";
+    let formatted = super::format_source(text);
+    assert_eq!(
+        formatted,
+        ".macro lda v\n    ADD #v\n.endm\n    lda 1\n    LDA #1\nbrk .equ 2\n    STA (brk)\n"
+    );
+    assert_eq!(
+        compile(&formatted).unwrap().commands,
+        compile(text).unwrap().commands
+    );
+
+    // Invalid lines stay as they are, comment markers inside strings are no comments
+    let text = "  LDA  $ 3   \n.include \"a//b.asm\"   // path\n";
+    assert_eq!(
+        super::format_source(text),
+        "  LDA  $ 3\n.include \"a//b.asm\" // path\n"
+    );
+    assert_eq!(super::format_source(""), "");
+
+    // A label named `brk` keeps its case
+    assert_eq!(
+        super::format_source("brk: nop\nbrk jmp brk"),
+        "brk: NOP\n     BRK JMP brk\n"
+    );
+}
+
+#[test]
+fn test_analysis_session() {
+    use super::{AnalysisSession, TextEdit, TextPosition};
+
+    let pieces = [
+        "", "\n", "start: ", "JMP start", "    LDA #1", "STA (2)\n", "// comment\n", "BRZ ", "ä",
+        "x .equ 3\n", "ADD #x", "#20", ".data 1 = 2\n", "lda", "\n\n  NOP 0", "LAD (1)", "\"",
+        ".macro m a\nADD #a\n.endm\n", "m 2\n", "loop: BRK BRN loop\n",
+    ];
+    let mut text = String::from("start: LDA #1\n    STA (1)\n    JMP start\n");
+    let mut session = AnalysisSession::new(&text);
+
+    // Deterministic pseudo random edits, the reference text is edited by hand
+    let mut seed: u64 = 7;
+    let mut next = |limit: usize| {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (seed >> 33) as usize % limit.max(1)
+    };
+    for _ in 0..300 {
+        let lines: Vec<&str> = text.split('\n').collect();
+        let position = |next: &mut dyn FnMut(usize) -> usize| {
+            let line = next(lines.len());
+            let mut column = next(lines[line].len() + 1);
+            while !lines[line].is_char_boundary(column) {
+                column -= 1;
+            }
+            let offset: usize = lines[..line].iter().map(|line| line.len() + 1).sum();
+            (TextPosition::new(line, column), offset + column)
+        };
+        let (mut start, mut end) = (position(&mut next), position(&mut next));
+        if end < start {
+            std::mem::swap(&mut start, &mut end);
+        }
+        let replacement = pieces[next(pieces.len())];
+
+        text.replace_range(start.1..end.1, replacement);
+        session.edit(&TextEdit::new(start.0, end.0, replacement));
+        assert_eq!(session.text(), text);
+        if next(4) == 0 {
+            assert_eq!(*session.result(), compile(&text), "{:?}", text);
+            let symbols = super::find_symbols("", &files(&[("", &text)]));
+            assert_eq!(session.symbols(), symbols);
+        }
+    }
+    assert_eq!(*session.result(), compile(&text));
+
+    // Positions behind the end are moved to the end of the line or text
+    let mut session = AnalysisSession::new("NOP\nNOP");
+    session.edit(&TextEdit::new(TextPosition::new(0, 9), TextPosition::new(0, 9), " // a"));
+    session.edit(&TextEdit::new(TextPosition::new(5, 0), TextPosition::new(6, 0), "\nSTA (16)"));
+    assert_eq!(session.text(), "NOP // a\nNOP\nSTA (16)");
+    assert_eq!(session.diagnostics().errors.len(), 1);
+    assert_eq!(session.symbols().symbols.len(), 0);
+}
+
+#[test]
+fn test_analysis_session_matches_compile() {
+    use super::{AnalysisSession, TextEdit, TextPosition};
+
+    // Macros and names used by the lines below, whole line edits keep the first lines
+    let header = "\n.macro m a\nl: ADD #a\nBRC l\n.endm\n.macro k\nm 1\nl: BRN l\n.endm\nx .equ 3\nloop: NOP\n";
+    let header_lines = header.matches('\n').count();
+    let lines = [
+        "", "", "NOP", "LDA #1", "ADD #x", "m 2", "k", "lbl: k", "STA (2)", ".data 1 = x",
+        "BRK NOP", "BRK", "JMP loop", "BRZ loop", "BRC lbl", "JMP 9",
+    ];
+    let bodies = [(2, ["l: ADD #a", "l: SUB #a", "l: JMP l"]), (6, ["m 1", "NOP", "LDA #x"])];
+    // Pieces of lines, typing them mostly creates errors
+    let pieces = [
+        "", "", "\n", "\n\n", "start: ", "JMP start", "BRZ loop", "    LDA #1\n", "STA (2)\n",
+        "x .equ 3\n", "x .equ 4\n", "ADD #x\n", "LDA (x)", ".data 1 = x\n", "loop: ", "loop:\n\n",
+        "BRK ", "BRK\n", "brk: NOP\n", ".macro n\n", ".macro m a\n", ".macro m a b\n", ".endm\n", ".endm",
+        "m 2\n", "m 2 3\n", "n\n", "lbl: n\n", ".macro n\nl: BRN l\nm 1\n.endm\n",
+        ".macro k\nm 5\n.endm\n", "k\n", "ADD #a\n", "x: JMP x\n", ".include \"a.asm\"\n",
+        ".include \"\"", "mm 1\n", "// m\n", "\"m", "LAD (1)\n", "ä", "NOP 0\n", ":",
+    ];
+    for seed in 1..=8 {
+        let mut text = format!("{header}start: m 1\n    STA (1)\n    JMP start\n");
+        let mut session = AnalysisSession::new(&text);
+        assert_eq!(*session.result(), compile(&text));
+
+        // Deterministic pseudo random edits, the reference text is edited by hand
+        let whole_lines = seed % 2 == 0;
+        let mut seed: u64 = seed;
+        let mut next = |limit: usize| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % limit.max(1)
+        };
+        for _ in 0..200 {
+            let text_lines: Vec<&str> = text.split('\n').collect();
+            let offset = |line: usize, column: usize| {
+                let start: usize = text_lines[..line].iter().map(|line| line.len() + 1).sum();
+                (TextPosition::new(line, column), start + column)
+            };
+            let (start, end, replacement) = if whole_lines && next(4) == 0 {
+                // Changed macro bodies expand to other commands
+                let (line, variants) = bodies[next(bodies.len())];
+                let replacement = format!("{}\n", variants[next(variants.len())]);
+                (offset(line, 0), offset(line + 1, 0), replacement)
+            } else if whole_lines {
+                // Short programs stay in the range of the operands
+                let body = text_lines.len() - 1 - header_lines;
+                let first = header_lines + next(body + 1);
+                let last = (first + next(3)).min(text_lines.len() - 1);
+                let replacement = match lines[next(lines.len())] {
+                    line if body < 6 && !line.is_empty() => format!("{line}\n"),
+                    _ => String::new(),
+                };
+                (offset(first, 0), offset(last, 0), replacement)
+            } else {
+                // Most edits stay in one or two lines like typing in an editor
+                let position = |next: &mut dyn FnMut(usize) -> usize, line: usize| {
+                    let line = line.min(text_lines.len() - 1);
+                    let mut column = next(text_lines[line].len() + 1);
+                    while !text_lines[line].is_char_boundary(column) {
+                        column -= 1;
+                    }
+                    offset(line, column)
+                };
+                let first = next(text_lines.len());
+                let start = position(&mut next, first);
+                let last = first + next(3);
+                let end = position(&mut next, last);
+                let (start, end) = if end.1 < start.1 { (end, start) } else { (start, end) };
+                (start, end, pieces[next(pieces.len())].to_string())
+            };
+
+            text.replace_range(start.1..end.1, &replacement);
+            session.edit(&TextEdit::new(start.0, end.0, &replacement));
+            assert_eq!(session.text(), text);
+            assert_eq!(*session.result(), compile(&text), "{:?}", text);
+            if next(8) == 0 {
+                let symbols = super::find_symbols("", &files(&[("", &text)]));
+                assert_eq!(session.symbols(), symbols);
+            }
+        }
+    }
+}
@@ -1,11 +1,29 @@
 mod backend;
 mod commands;
+mod diagnostic;
+mod encoding;
 mod error;
 mod frontend;
 mod nibble;
+mod program;
 
-pub use error::{ParseError, ParseErrorReport, ParseErrorType};
-pub use backend::{Runtime, StatusBits};
-pub use commands::Command;
-pub use frontend::compile;
+pub use error::{
+    DecodeError, EncodeError, InvalidOpcode, Line, MacroInvocation, ParseError, ParseErrorReport,
+    ParseErrorType, RuntimeError, Severity,
+};
+pub use backend::{
+    Breakpoint, MemoryLayout, Runtime, StatusBits, StepOutcome, StopReason, Trace, TraceRow,
+    WatchCondition, Watchpoint, WatchpointHit, DEFAULT_HISTORY_LIMIT,
+};
+pub use commands::{
+    get_instruction_attribute, Command, Instruction, InstructionAttribute, MNEMONICS,
+};
+pub use encoding::{decode, decode_bytes, disassemble, encode, encode_bytes};
+pub use frontend::{
+    compile, compile_with, compile_with_loader, find_symbols, format_source, rebuild,
+    tokenize_lossless, AnalysisSession, CompileOptions, FileLoader, LosslessToken, SourceLoader,
+    SymbolKind, SymbolOccurrence, SymbolTable, TextEdit, TextPosition, Token, TokenType, Trivia,
+    TriviaKind,
+};
 pub use nibble::Nibble;
+pub use program::Program;
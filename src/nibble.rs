@@ -21,6 +21,9 @@ use std::ops::{Add, Sub};
 pub struct Nibble(u8);
 
 impl Nibble {
+    /// Largest value a nibble can hold without the carry bit
+    pub const MAX: u8 = 0b00001111;
+
     #[inline]
     pub fn get_value(&self) -> u8 {
         self.0 & 0b00001111
@@ -47,7 +50,6 @@ impl Nibble {
         self.get_value() as i32
     }
 
-    #[allow(unused)]
     pub fn as_signed(&self) -> i32 {
         if self.has_negative() {
             return -((self.arithmetic_complement()) as i32);
@@ -89,21 +91,21 @@ impl From<i32> for Nibble {
     }
 }
 
-impl Into<usize> for Nibble {
-    fn into(self) -> usize {
-        (self.get_value()) as usize
+impl From<Nibble> for usize {
+    fn from(value: Nibble) -> Self {
+        (value.get_value()) as usize
     }
 }
 
-impl Into<u8> for Nibble {
-    fn into(self) -> u8 {
-        self.get_value()
+impl From<Nibble> for u8 {
+    fn from(value: Nibble) -> Self {
+        value.get_value()
     }
 }
 
-impl Into<i32> for Nibble {
-    fn into(self) -> i32 {
-        (self.get_value()) as i32
+impl From<Nibble> for i32 {
+    fn from(value: Nibble) -> Self {
+        (value.get_value()) as i32
     }
 }
 
@@ -149,9 +151,9 @@ mod tests {
         assert!(result.as_signed() == 7, "{:?}", result);
         assert!(result.as_unsigned() == 7, "{:?}", result);
 
-        assert!(result.has_carry() == false, "{:?}", result);
-        assert!(result.has_negative() == false, "{:?}", result);
-        assert!(result.is_zero() == false, "{:?}", result);
+        assert!(!result.has_carry(), "{:?}", result);
+        assert!(!result.has_negative(), "{:?}", result);
+        assert!(!result.is_zero(), "{:?}", result);
     }
 
     #[test]
@@ -164,9 +166,9 @@ mod tests {
         assert!(result.as_unsigned() == 8, "{:?}", result);
         assert!(result.as_signed() == -8, "{:?}", result);
 
-        assert!(result.has_carry() == false, "{:?}", result);
-        assert!(result.has_negative() == true, "{:?}", result);
-        assert!(result.is_zero() == false, "{:?}", result);
+        assert!(!result.has_carry(), "{:?}", result);
+        assert!(result.has_negative(), "{:?}", result);
+        assert!(!result.is_zero(), "{:?}", result);
     }
 
     #[test]
@@ -179,9 +181,9 @@ mod tests {
         assert!(result.as_unsigned() == 3, "{:?}", result);
         assert!(result.as_signed() == 3, "{:?}", result);
 
-        assert!(result.has_carry() == true, "{:?}", result);
-        assert!(result.has_negative() == false, "{:?}", result);
-        assert!(result.is_zero() == false, "{:?}", result);
+        assert!(result.has_carry(), "{:?}", result);
+        assert!(!result.has_negative(), "{:?}", result);
+        assert!(!result.is_zero(), "{:?}", result);
     }
 
     #[test]
@@ -194,8 +196,8 @@ mod tests {
         assert!(result.as_unsigned() == 14, "{:?}", result);
         assert!(result.as_signed() == -2, "{:?}", result);
 
-        assert!(result.has_carry() == false, "{:?}", result);
-        assert!(result.has_negative() == true, "{:?}", result);
-        assert!(result.is_zero() == false, "{:?}", result);
+        assert!(!result.has_carry(), "{:?}", result);
+        assert!(result.has_negative(), "{:?}", result);
+        assert!(!result.is_zero(), "{:?}", result);
     }
 }
@@ -0,0 +1,31 @@
+use crate::commands::Command;
+use crate::error::ParseErrorReport;
+use crate::nibble::Nibble;
+
+/// Result of [`compile`](crate::compile), ready to be loaded into a [`Runtime`](crate::Runtime)
+///
+/// # Example
+/// ```
+/// # use asim::{compile, Runtime};
+/// let program = compile(".data 2 = 5\nLDA (2)").unwrap();
+/// assert_eq!(program.commands.len(), 1);
+/// assert_eq!(program.registers[2], 5);
+///
+/// let mut runtime = Runtime::from_program(program);
+/// runtime.step().unwrap();
+/// assert_eq!(runtime.get_accumulator(), 5);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Program {
+    /// Executable commands
+    pub commands: Vec<Command>,
+    /// Names of the compiled files, [`Command::file`] is an index into this list
+    ///
+    /// The first file includes the others. It has an empty name if the program was
+    /// compiled from a string.
+    pub files: Vec<String>,
+    /// Initial contents of the registers, set with `.data address = value`
+    pub registers: Vec<Nibble>,
+    /// Warnings of the compilation, the report contains no errors
+    pub diagnostics: ParseErrorReport,
+}